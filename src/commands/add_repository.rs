@@ -1,65 +1,106 @@
-use crate::git::clone::clone;
-use crate::util::structs::{RTPMConfig, RepositoryManifest};
+use crate::git::clone::clone_from_mirrors;
+use crate::git::revision::default_branch;
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::signature::{check_repository_signature, INDEX_FILE, INDEX_SIGNATURE_FILE};
+use crate::util::structs::{RTPMConfig, RTPMConfigRepositoryElement, RepositoryManifest};
 use crate::util::utils::{read_json_file, save_json_to_file};
 use clap::ArgMatches;
 use colored::Colorize;
 use std::path::PathBuf;
 
-pub fn add_repository(matches: &ArgMatches) {
-    let repository: &str = matches.get_one::<String>("repository").unwrap_or_else(|| {
-        println!("{}", "You have not filled a repository.".red().bold());
-        std::process::exit(22);
-    });
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+pub fn add_repository(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let urls: Vec<String> = matches
+        .get_many::<String>("repository")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    if urls.is_empty() {
+        return Err(RtpmError::InvalidInput(
+            "You have not filled a repository.".to_owned(),
+        ));
+    }
+    let rtpm_config_path: PathBuf = paths.rtpm_config();
+    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path)?;
 
-    let repositories_path: PathBuf = dirs::data_dir().unwrap().join("rtop").join("repositories");
+    let repositories_path: PathBuf = paths.repositories();
     let temp_path: PathBuf = repositories_path.join("temp");
 
     if temp_path.exists() {
-        std::fs::remove_dir_all(&temp_path).unwrap();
+        std::fs::remove_dir_all(&temp_path)?;
     }
-    std::fs::create_dir(&temp_path).unwrap();
+    std::fs::create_dir(&temp_path)?;
 
     println!(":: {}", "Downloading the repository...".green());
 
-    clone(repository, &temp_path);
+    let mirror: String = match clone_from_mirrors(&urls, &temp_path) {
+        Ok(mirror) => mirror,
+        Err(error) => {
+            drop(std::fs::remove_dir_all(&temp_path));
+            return Err(error);
+        }
+    };
 
     let manifest_path: PathBuf = temp_path.join("manifest.json");
 
     if !manifest_path.exists() {
-        println!(":: {}", "This is not a plugin repository!".red().bold());
-
         println!(":: {}", "Cleaning...".green());
-        std::fs::remove_dir_all(temp_path).unwrap();
+        std::fs::remove_dir_all(temp_path)?;
         println!(":: {}", "Cleaning completed!".green());
 
-        println!(":: {}", "Exit...".blue());
-        std::process::exit(22);
+        return Err(RtpmError::InvalidInput(
+            "This is not a plugin repository!".to_owned(),
+        ));
     }
 
-    let repository_manifest: RepositoryManifest = read_json_file(&manifest_path);
+    let repository_manifest: RepositoryManifest = match read_json_file(&manifest_path) {
+        Ok(repository_manifest) => repository_manifest,
+        Err(error) => {
+            std::fs::remove_dir_all(temp_path)?;
+            return Err(error);
+        }
+    };
 
     if rtpm_config.repositories.contains(&repository_manifest.id) {
-        println!(
-            ":: {}",
-            "This repository is already installed!".red().bold()
-        );
-
         println!(":: {}", "Cleaning...".green());
-        std::fs::remove_dir_all(temp_path).unwrap();
+        std::fs::remove_dir_all(temp_path)?;
         println!(":: {}", "Cleaning completed!".green());
 
-        std::process::exit(22);
+        return Err(RtpmError::InvalidInput(
+            "This repository is already installed!".to_owned(),
+        ));
+    }
+    let mut details: RTPMConfigRepositoryElement = RTPMConfigRepositoryElement {
+        branch: Some(default_branch(&temp_path)?),
+        mirror: Some(mirror),
+        ..RTPMConfigRepositoryElement::default()
+    };
+    let index: Vec<u8> = std::fs::read(temp_path.join(INDEX_FILE)).unwrap_or_default();
+    let signature: Option<String> =
+        std::fs::read_to_string(temp_path.join(INDEX_SIGNATURE_FILE)).ok();
+    if let Err(error) = check_repository_signature(
+        &repository_manifest.id,
+        repository_manifest.public_key.as_deref(),
+        &index,
+        signature.as_deref(),
+        &mut details,
+    ) {
+        println!(":: {}", "Cleaning...".green());
+        std::fs::remove_dir_all(temp_path)?;
+        println!(":: {}", "Cleaning completed!".green());
+        return Err(error);
     }
     let new_path: PathBuf = repositories_path.join(repository_manifest.id.clone());
 
-    std::fs::rename(temp_path, new_path).unwrap();
+    std::fs::rename(temp_path, new_path)?;
 
     println!(":: {}", "Linking repository to RTPM...".green());
+    rtpm_config
+        .repository_details
+        .insert(repository_manifest.id.clone(), details);
     rtpm_config.repositories.push(repository_manifest.id);
-    save_json_to_file(&rtpm_config, rtpm_config_path);
+    save_json_to_file(&rtpm_config, rtpm_config_path)?;
     println!(":: {}", "Plugin repository to RTPM!".green());
 
     println!(":: {}", "Repository added!".green().bold());
+    Ok(())
 }
@@ -0,0 +1,75 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::structs::RTPMConfig;
+use crate::util::utils::{
+    contain_clap_arg, convert_to_readable_unity, directory_size, read_json_file, save_json_to_file,
+};
+use clap::ArgMatches;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+fn prune(name: &str, path: &Path) -> Result<(), RtpmError> {
+    if !path.exists() {
+        println!(":: {}", format!("The {} is already empty.", name).green());
+        return Ok(());
+    }
+    let size: u64 = directory_size(path)?;
+    println!(":: {}", format!("Pruning the {}...", name).green());
+    std::fs::remove_dir_all(path)?;
+    println!(
+        ":: {}",
+        format!(
+            "The {} has been pruned, {} freed!",
+            name,
+            convert_to_readable_unity(size as f64)
+        )
+        .green()
+    );
+    Ok(())
+}
+
+pub fn cache(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let build_cache_path: PathBuf = paths.build_cache();
+    let source_cache_path: PathBuf = paths.source_caches();
+
+    if contain_clap_arg("prune", matches)? {
+        prune("build cache", &build_cache_path)?;
+        if contain_clap_arg("sources", matches)? {
+            prune("source cache", &source_cache_path)?;
+        }
+        return Ok(());
+    }
+
+    let mut rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    if let Some(shared_target) = matches.get_one::<String>("shared-target") {
+        rtpm_config.build.shared_target_dir = shared_target == "on";
+        save_json_to_file(&rtpm_config, paths.rtpm_config())?;
+        println!(
+            ":: {}",
+            if rtpm_config.build.shared_target_dir {
+                "The plugins will now be built in the shared build cache."
+            } else {
+                "The plugins will now be built in their own directory."
+            }
+            .green()
+        );
+        return Ok(());
+    }
+    let shared_target: &str = if rtpm_config.build.shared_target_dir {
+        "Enabled"
+    } else {
+        "Disabled"
+    };
+    println!(
+        "{} {}\n{} {} ({})\n{} {} ({})\n",
+        "Shared target :".blue(),
+        shared_target.yellow(),
+        "Build cache   :".blue(),
+        convert_to_readable_unity(directory_size(&build_cache_path)? as f64).yellow(),
+        build_cache_path.display(),
+        "Source cache  :".blue(),
+        convert_to_readable_unity(directory_size(&source_cache_path)? as f64).yellow(),
+        source_cache_path.display(),
+    );
+    Ok(())
+}
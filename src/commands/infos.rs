@@ -1,11 +1,16 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
 use crate::util::structs::{PluginManifest, RTPMConfig, RepositoryManifest};
-use crate::util::utils::{contain_clap_arg, read_json_file, search_plugin};
+use crate::util::utils::{
+    contain_clap_arg, detect_rtop_version, read_json_file, rtop_requirement_matches, search_plugin,
+};
 use clap::ArgMatches;
 use colored::Colorize;
 use itertools::Itertools;
+use semver::Version;
 use std::path::PathBuf;
 
-fn repository_infos(repositories: Vec<String>) {
+fn repository_infos(repositories: Vec<String>, paths: &Paths) -> Result<(), RtpmError> {
     if repositories.len() > 1 {
         println!(
             ":: {}",
@@ -17,12 +22,9 @@ fn repository_infos(repositories: Vec<String>) {
         println!(":: {}", "Information about repository\n".yellow().bold());
     }
 
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
     for repository in repositories {
-        let repository_path: PathBuf = dirs::data_dir()
-            .unwrap()
-            .join("rtop")
-            .join("repositories")
-            .join(repository.clone());
+        let repository_path: PathBuf = paths.repository(&repository);
         if !repository_path.exists() {
             println!(
                 ":: {}",
@@ -37,17 +39,30 @@ fn repository_infos(repositories: Vec<String>) {
         }
 
         let repo_manifest: RepositoryManifest =
-            read_json_file(&repository_path.join("manifest.json"));
+            read_json_file(&repository_path.join("manifest.json"))?;
 
         let mut fallback_url: String = repo_manifest
-            .fallback_url
-            .unwrap_or_else(|| "No".to_owned());
-        if fallback_url.trim().is_empty() {
+            .mirrors()
+            .into_iter()
+            .filter(|mirror| mirror != repo_manifest.url.trim())
+            .join(", ");
+        if fallback_url.is_empty() {
             fallback_url = "No".to_owned();
         }
+        let mirror: String = rtpm_config
+            .repository_details
+            .get(&repository)
+            .and_then(|details| details.mirror.clone())
+            .unwrap_or_else(|| repo_manifest.url.clone());
+        let signing_key: String = rtpm_config
+            .repository_details
+            .get(&repository)
+            .and_then(|details| details.public_key.clone())
+            .or(repo_manifest.public_key)
+            .unwrap_or_else(|| "No".to_owned());
 
         println!(
-            "{} {}\n{} {}\n{} {}\n{} {}\n",
+            "{} {}\n{} {}\n{} {}\n{} {}\n{} {}\n{} {}\n",
             "Name         :".blue(),
             repo_manifest.name.yellow(),
             "Description  :".blue(),
@@ -56,11 +71,16 @@ fn repository_infos(repositories: Vec<String>) {
             repo_manifest.url.yellow(),
             "Fallback URL :".blue(),
             fallback_url.yellow(),
+            "Mirror used  :".blue(),
+            mirror.yellow(),
+            "Signing key  :".blue(),
+            signing_key.yellow(),
         );
     }
+    Ok(())
 }
 
-fn plugin_infos(plugins: Vec<String>) {
+fn plugin_infos(plugins: Vec<String>, paths: &Paths) -> Result<(), RtpmError> {
     if plugins.len() > 1 {
         println!(
             ":: {}",
@@ -72,17 +92,13 @@ fn plugin_infos(plugins: Vec<String>) {
         println!(":: {}", "Information about plugin\n".yellow().bold());
     }
 
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-
-    let rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    let rtop_version: Option<Version> = detect_rtop_version(paths);
 
     for plugin in plugins {
-        let repository_path: PathBuf = if let Some(repository_path) = search_plugin(
-            plugin.as_str(),
-            rtpm_config.clone(),
-            &rtpm_config_path,
-            false,
-        ) {
+        let repository_path: PathBuf = if let Some(repository_path) =
+            search_plugin(plugin.as_str(), &rtpm_config, paths, false)?
+        {
             repository_path
         } else {
             println!(
@@ -98,7 +114,7 @@ fn plugin_infos(plugins: Vec<String>) {
             &repository_path
                 .join("plugins")
                 .join(format!("{}.json", plugin)),
-        );
+        )?;
 
         let mut to_print: String = format!(
             "{} {}\n{} {}\n{} {}\n{} {}\n{} {}\n",
@@ -117,14 +133,12 @@ fn plugin_infos(plugins: Vec<String>) {
         if let Some(author) = plugin_manifest.author {
             to_print
                 .push_str(format!("{} {}\n", "Author       :".blue(), author.yellow()).as_str());
-        } else if plugin_manifest.authors.is_some()
-            && !plugin_manifest.authors.clone().unwrap().is_empty()
-        {
+        } else if let Some(authors) = plugin_manifest.authors.filter(|a| !a.is_empty()) {
             to_print.push_str(
                 format!(
                     "{} {}\n",
                     "Authors      :".blue(),
-                    plugin_manifest.authors.unwrap().join(", ").yellow()
+                    authors.join(", ").yellow()
                 )
                 .as_str(),
             );
@@ -135,46 +149,113 @@ fn plugin_infos(plugins: Vec<String>) {
         } else {
             to_print.push_str(format!("{} {}\n", "License      :".blue(), "No".yellow()).as_str());
         }
-        if plugin_manifest.arch.is_some() && !plugin_manifest.arch.clone().unwrap().is_empty() {
+        if let Some(arch) = plugin_manifest.arch.filter(|a| !a.is_empty()) {
+            to_print.push_str(
+                format!("{} {}\n", "Arch         :".blue(), arch.join(", ").yellow()).as_str(),
+            );
+        } else {
+            to_print.push_str(format!("{} {}\n", "Arch         :".blue(), "All".yellow()).as_str());
+        }
+        if let Some(os) = plugin_manifest.os.filter(|o| !o.is_empty()) {
+            to_print.push_str(
+                format!("{} {}\n", "OS           :".blue(), os.join(", ").yellow()).as_str(),
+            );
+        } else {
+            to_print.push_str(format!("{} {}\n", "OS           :".blue(), "All".yellow()).as_str());
+        }
+        if let Some(platforms) = plugin_manifest.platforms.filter(|p| !p.is_empty()) {
             to_print.push_str(
                 format!(
                     "{} {}\n",
-                    "Arch         :".blue(),
-                    plugin_manifest.arch.unwrap().join(", ").yellow()
+                    "Platforms    :".blue(),
+                    platforms.join(", ").yellow()
                 )
                 .as_str(),
             );
-        } else {
-            to_print.push_str(format!("{} {}\n", "Arch         :".blue(), "All".yellow()).as_str());
         }
-        if plugin_manifest.os.is_some() && !plugin_manifest.os.clone().unwrap().is_empty() {
+        if !plugin_manifest.features.is_empty() {
             to_print.push_str(
                 format!(
                     "{} {}\n",
-                    "OS           :".blue(),
-                    plugin_manifest.os.unwrap().join(", ").yellow()
+                    "Features     :".blue(),
+                    plugin_manifest.features.join(", ").yellow()
+                )
+                .as_str(),
+            );
+        }
+        if !plugin_manifest.artifacts.is_empty() {
+            to_print.push_str(
+                format!(
+                    "{} {}\n",
+                    "Prebuilt     :".blue(),
+                    plugin_manifest.artifacts.keys().join(", ").yellow()
+                )
+                .as_str(),
+            );
+        }
+        if let Some(requirement) = plugin_manifest.rtop {
+            let compatibility: String = match &rtop_version {
+                Some(version) => match rtop_requirement_matches(&requirement, version) {
+                    Ok(true) => format!("compatible with the installed v{}", version),
+                    Ok(false) => format!("incompatible with the installed v{}", version)
+                        .red()
+                        .to_string(),
+                    Err(_) => "not a valid semver range".red().to_string(),
+                },
+                None => "unable to detect the installed version".to_owned(),
+            };
+            to_print.push_str(
+                format!(
+                    "{} {} ({})\n",
+                    "Rtop         :".blue(),
+                    requirement.yellow(),
+                    compatibility
                 )
                 .as_str(),
             );
         } else {
-            to_print.push_str(format!("{} {}\n", "OS           :".blue(), "All".yellow()).as_str());
+            to_print.push_str(format!("{} {}\n", "Rtop         :".blue(), "All".yellow()).as_str());
         }
 
         println!("{}", to_print);
     }
+    Ok(())
 }
 
-fn plugin_list() {
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+fn plugin_list(paths: &Paths) -> Result<(), RtpmError> {
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    let rtop_version: Option<Version> = detect_rtop_version(paths);
 
     let mut plugins_list: String = String::new();
+    let mut incompatible_plugins: Vec<String> = Vec::new();
     for plugin in rtpm_config.plugins {
+        if let (Some(requirement), Some(version)) = (&plugin.rtop, &rtop_version) {
+            match rtop_requirement_matches(requirement, version) {
+                Ok(true) => {}
+                Ok(false) => incompatible_plugins
+                    .push(format!("{} (requires Rtop {})", plugin.name, requirement)),
+                Err(_) => incompatible_plugins.push(format!(
+                    "{} (invalid Rtop requirement {})",
+                    plugin.name, requirement
+                )),
+            }
+        }
+        let mut commit: String = if plugin.artifact.is_some() {
+            ", prebuilt".to_owned()
+        } else {
+            plugin.commit.map_or_else(String::new, |commit| {
+                format!(" @ {}", &commit[..commit.len().min(7)])
+            })
+        };
+        if let Some(pin) = plugin.pin {
+            commit.push_str(&format!(", pinned to the {}", pin));
+        }
         plugins_list.push_str(
             format!(
-                "{} (v{}) - {}\n",
+                "{} (v{}{}) - {}\n",
                 plugin.name.yellow().bold(),
                 plugin.version.bold(),
+                commit,
                 plugin.repo
             )
             .as_str(),
@@ -185,21 +266,28 @@ fn plugin_list() {
     } else {
         println!("{}", plugins_list);
     }
+    if let Some(version) = rtop_version.filter(|_| !incompatible_plugins.is_empty()) {
+        println!(
+            "{}\n{}",
+            format!(
+                "The following plugins are incompatible with the installed Rtop v{}:",
+                version
+            )
+            .red()
+            .bold(),
+            incompatible_plugins.join("\n")
+        );
+    }
+    Ok(())
 }
 
-fn repositories_list() {
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+fn repositories_list(paths: &Paths) -> Result<(), RtpmError> {
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
 
     let mut repositories_list: String = String::new();
     for repository in rtpm_config.repositories {
-        let repository_path: PathBuf = dirs::data_dir()
-            .unwrap()
-            .join("rtop")
-            .join("repositories")
-            .join(repository)
-            .join("manifest.json");
-        let repository_manifest: RepositoryManifest = read_json_file(&repository_path);
+        let repository_path: PathBuf = paths.repository(&repository).join("manifest.json");
+        let repository_manifest: RepositoryManifest = read_json_file(&repository_path)?;
 
         repositories_list.push_str(
             format!(
@@ -215,37 +303,36 @@ fn repositories_list() {
     } else {
         println!("{}", repositories_list);
     }
+    Ok(())
 }
 
-pub fn infos(matches: &ArgMatches) {
-    let plugin_arg: bool = contain_clap_arg("plugin", matches);
-    let repository_arg: bool = contain_clap_arg("repository", matches);
-    let list_arg: bool = contain_clap_arg("list", matches);
+pub fn infos(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let plugin_arg: bool = contain_clap_arg("plugin", matches)?;
+    let repository_arg: bool = contain_clap_arg("repository", matches)?;
+    let list_arg: bool = contain_clap_arg("list", matches)?;
 
     if repository_arg && list_arg {
-        repositories_list();
+        repositories_list(paths)
     } else if plugin_arg && list_arg {
-        plugin_list();
+        plugin_list(paths)
     } else {
         let plugin_or_repository: Vec<String> = matches
             .get_many::<String>("elements")
-            .unwrap_or_else(|| {
-                println!(
-                    "{}",
-                    "You have not filled in any plugin or repository."
-                        .red()
-                        .bold()
-                );
-                std::process::exit(22);
-            })
+            .ok_or_else(|| {
+                RtpmError::InvalidInput(
+                    "You have not filled in any plugin or repository.".to_owned(),
+                )
+            })?
             .cloned()
             .unique()
             .collect();
 
         if repository_arg {
-            repository_infos(plugin_or_repository);
+            repository_infos(plugin_or_repository, paths)
         } else if plugin_arg {
-            plugin_infos(plugin_or_repository);
+            plugin_infos(plugin_or_repository, paths)
+        } else {
+            Ok(())
         }
     }
 }
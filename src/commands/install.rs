@@ -1,56 +1,182 @@
-use crate::git::clone::clone;
+use crate::git::clone::{clone, shallow_clone};
+use crate::git::revision::{
+    checkout_commit, checkout_expected_commit, checkout_pin, default_branch, head_commit,
+    remote_default_branch, verify_commit,
+};
+use crate::git::source_cache::{restore_source_cache, store_source_cache};
 use crate::git::update_repositories::update_repositories;
 use crate::git::updates_packages::update_packages;
+use crate::util::artifact::{fetch_artifact, select_artifact, ArtifactBase};
+use crate::util::error::RtpmError;
+use crate::util::network::{http_client, is_offline};
+use crate::util::paths::Paths;
+use crate::util::progress::default_jobs;
+use crate::util::resolver::{resolve_dependencies, Requirement, ResolvedPlugin};
 use crate::util::structs::{
-    PluginManifest, RTPMConfig, RTPMConfigPluginElement, RtopConfig, RtopConfigPlugins,
+    BuildOptions, PluginArtifact, PluginManifest, PluginPin, RTPMConfig, RTPMConfigPluginElement,
+    RtopConfig, RtopConfigPlugins,
 };
 use crate::util::utils::{
-    build_cargo_project, contain_clap_arg, get_raw_url, read_json_file, save_json_to_file,
-    search_plugin, user_input_choice, verify_device_specification,
+    build_plugin, contain_clap_arg, current_timestamp, detect_rtop_version, get_raw_url,
+    keep_failed_build, parse_plugin_pin, read_json_file, save_json_to_file, search_plugin,
+    sha256_hex, user_input_choice, verify_device_specification, verify_rtop_compatibility,
 };
 use clap::ArgMatches;
 use colored::Colorize;
 use itertools::Itertools;
-use std::fs::ReadDir;
-use std::path::PathBuf;
+use reqwest::blocking::Response;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use url::Url;
 
-fn install_plugin(plugin_manifest: PluginManifest, plugin_type: i8) -> bool {
-    let plugin_repository_path: PathBuf = dirs::data_dir()
-        .unwrap()
-        .join("rtop")
-        .join("plugins")
-        .join(plugin_manifest.id.clone());
-    let author_string: String = if plugin_manifest.authors.is_some()
-        && !plugin_manifest.authors.clone().unwrap().is_empty()
+struct PluginSource {
+    plugin_type: i8,
+    // The URL cloned to build the plugin, recorded as the plugin `repo`.
+    url: String,
+    repository: Option<String>,
+    manifest_hash: String,
+    commit: Option<String>,
+    pin: Option<PluginPin>,
+    expected_commit: Option<String>,
+    artifact_base: Option<ArtifactBase>,
+    build_from_source: bool,
+    // The sha256 recorded by `--locked`, only this artifact may be installed.
+    locked_artifact: Option<String>,
+    build_options: BuildOptions,
+    // Moves the staging directory to `failed/<id>` instead of deleting it when the install fails.
+    keep_failed: bool,
+}
+
+// The build options given to `rtpm -S`, `features` is `None` without `--features`.
+#[derive(Default)]
+struct BuildRequest {
+    options: BuildOptions,
+    features: Option<Vec<String>>,
+    keep_failed: bool,
+}
+
+impl BuildRequest {
+    fn from_matches(matches: &ArgMatches) -> Result<Self, RtpmError> {
+        let mut env: BTreeMap<String, String> = BTreeMap::new();
+        for variable in matches.get_many::<String>("env").into_iter().flatten() {
+            let (key, value): (&str, &str) = variable.split_once('=').ok_or_else(|| {
+                RtpmError::InvalidInput(format!(
+                    "The environment override {} must be written KEY=VALUE.",
+                    variable
+                ))
+            })?;
+            env.insert(key.to_owned(), value.to_owned());
+        }
+        Ok(Self {
+            options: BuildOptions {
+                features: Vec::new(),
+                no_default_features: contain_clap_arg("no-default-features", matches)?,
+                profile: matches.get_one::<String>("profile").cloned(),
+                target: matches.get_one::<String>("target").cloned(),
+                env,
+            },
+            features: matches.get_many::<String>("features").map(|features| {
+                features
+                    .flat_map(|feature| feature.split([',', ' ']))
+                    .filter(|feature| !feature.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            }),
+            keep_failed: contain_clap_arg("keep-failed", matches)?,
+        })
+    }
+
+    // A prebuilt artifact can't honour custom build options.
+    fn is_custom(&self) -> bool {
+        self.features.is_some() || self.options != BuildOptions::default()
+    }
+
+    fn options_for(&self, plugin_manifest: &PluginManifest) -> BuildOptions {
+        BuildOptions {
+            features: self
+                .features
+                .clone()
+                .unwrap_or_else(|| plugin_manifest.features.clone()),
+            ..self.options.clone()
+        }
+    }
+}
+
+struct StagedPlugin {
+    shared_lib_relative_path: PathBuf,
+    commit: Option<String>,
+    branch: Option<String>,
+    artifact: Option<String>,
+}
+
+fn install_plugin(
+    plugin_manifest: &PluginManifest,
+    source: &PluginSource,
+    paths: &Paths,
+) -> Result<bool, RtpmError> {
+    let plugin_repository_path: PathBuf = paths.plugin(&plugin_manifest.id);
+    let author_string: String = if let Some(authors) = plugin_manifest
+        .authors
+        .clone()
+        .filter(|authors| !authors.is_empty())
     {
-        plugin_manifest.authors.clone().unwrap().join(", ")
+        authors.join(", ")
     } else if let Some(author) = plugin_manifest.author.clone() {
         author
     } else {
         "an unknown".to_owned()
     };
 
-    if !verify_device_specification(&plugin_manifest) {
+    if let Err(reason) = verify_device_specification(plugin_manifest) {
         println!(
             ":: {}",
-            "The author of this plugin has excluded your OS or architecture from the compatibility list.".yellow().bold()
+            format!(
+                "The author of this plugin has excluded your platform from the compatibility list: {}.",
+                reason
+            )
+            .yellow()
+            .bold()
         );
         print!(
             ":: {} ",
             "You can still continue if you wish (the compilation of the plugin may fail) (y/n)"
                 .purple()
         );
-        if !user_input_choice() {
-            println!(":: {}", "Exiting...".blue());
-            std::process::exit(0);
+        if !user_input_choice()? {
+            return Err(RtpmError::UserAbort);
         }
     }
 
     if plugin_repository_path.exists() {
         println!(":: {}", format!("The plugin {} by {} is already installed! You can use the {} command to update it.", plugin_manifest.name, author_string, "rtpm -Sud".bold()).red());
-        return false;
+        return Ok(false);
     }
+    verify_rtop_compatibility(
+        &plugin_manifest.id,
+        plugin_manifest.rtop.as_deref(),
+        detect_rtop_version(paths).as_ref(),
+    )?;
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    if let Some(conflicting) = rtpm_config.plugins.iter().find(|r| {
+        plugin_manifest.conflicts.contains(&r.id) || r.conflicts.contains(&plugin_manifest.id)
+    }) {
+        return Err(RtpmError::Dependency(format!(
+            "The plugin {} conflicts with the installed plugin {}.",
+            plugin_manifest.id, conflicting.id
+        )));
+    }
+    let build_target_dir: Option<PathBuf> = rtpm_config
+        .build
+        .shared_target_dir
+        .then(|| paths.build_cache());
+    let rtop_config_path: PathBuf = paths.rtop_config();
+    if !rtop_config_path.exists() {
+        return Err(RtpmError::NotFound(format!(
+            "The Rtop config file: {} does not exist, you must launch Rtop before using RtopPluginManager.",
+            rtop_config_path.display()
+        )));
+    }
+
     println!(
         ":: {}",
         format!(
@@ -59,97 +185,356 @@ fn install_plugin(plugin_manifest: PluginManifest, plugin_type: i8) -> bool {
         )
         .green()
     );
-    clone(&plugin_manifest.url, plugin_repository_path.as_path());
-    println!(
-        ":: {}",
-        "Launching the compilation of the plugin...\n".green()
-    );
+    let staging_path: PathBuf = paths.staging().join(&plugin_manifest.id);
+    if staging_path.exists() {
+        std::fs::remove_dir_all(&staging_path)?;
+    }
+    std::fs::create_dir_all(paths.staging())?;
+    let staged_plugin: StagedPlugin = match stage_plugin(
+        plugin_manifest,
+        source,
+        &staging_path,
+        &paths.source_cache(&plugin_manifest.id),
+        build_target_dir.as_deref(),
+        &paths.build_log(&plugin_manifest.id),
+    ) {
+        Ok(staged_plugin) => staged_plugin,
+        Err(error) => {
+            if source.keep_failed && staging_path.exists() {
+                keep_failed_build(&staging_path, &paths.failed_build(&plugin_manifest.id))?;
+                return Err(error);
+            }
+            println!(
+                ":: {}",
+                "Cleaning the previously installed plugin...".green()
+            );
+            if staging_path.exists() {
+                std::fs::remove_dir_all(&staging_path)?;
+            }
+            println!(":: {}", "Cleaning finished!".green());
+            return Err(error);
+        }
+    };
 
-    let plugin_cargo_toml_path: PathBuf = dirs::data_dir()
-        .unwrap()
-        .join("rtop")
-        .join("plugins")
-        .join(plugin_manifest.id.clone())
-        .join("Cargo.toml");
+    std::fs::rename(&staging_path, &plugin_repository_path)?;
 
-    let cargo_build: bool = build_cargo_project(&plugin_cargo_toml_path);
-    if !cargo_build {
-        println!(":: {}", "An error occurred during compilation!".red());
+    let element: RTPMConfigPluginElement = RTPMConfigPluginElement {
+        id: plugin_manifest.id.clone(),
+        name: plugin_manifest.name.clone(),
+        version: plugin_manifest.version.clone(),
+        repo: source.url.clone(),
+        plugin_type: source.plugin_type,
+        repository: source.repository.clone(),
+        commit: staged_plugin.commit,
+        manifest_hash: Some(source.manifest_hash.clone()),
+        build_timestamp: Some(current_timestamp()),
+        pin: source.pin.clone(),
+        branch: staged_plugin.branch,
+        dependencies: plugin_manifest.dependencies.clone(),
+        conflicts: plugin_manifest.conflicts.clone(),
+        rtop: plugin_manifest.rtop.clone(),
+        build_options: if staged_plugin.artifact.is_some() {
+            BuildOptions::default()
+        } else {
+            source.build_options.clone()
+        },
+        artifact: staged_plugin.artifact,
+    };
+    if let Err(error) = link_plugin(
+        element,
+        &plugin_repository_path.join(staged_plugin.shared_lib_relative_path),
+        paths,
+    ) {
         println!(
             ":: {}",
             "Cleaning the previously installed plugin...".green()
         );
-        std::fs::remove_dir_all(plugin_repository_path).unwrap();
-        println!(":: {}", "Cleaning finished, program exit...".green());
-        std::process::exit(0);
+        std::fs::remove_dir_all(&plugin_repository_path)?;
+        println!(":: {}", "Cleaning finished!".green());
+        return Err(error);
     }
+    println!(":: {}", format!("The plugin {} is now installed! You can execute rtpm -Ip {} to get info about this plugin.", plugin_manifest.name, plugin_manifest.id).green());
+    Ok(true)
+}
 
-    println!("\n:: {}", "Plugin compiled!".green());
-    println!(":: {}", "Linking plugin to Rtop...".green());
-
-    let rtop_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("config.json");
-    if !rtop_config_path.exists() {
+fn stage_plugin(
+    plugin_manifest: &PluginManifest,
+    source: &PluginSource,
+    staging_path: &Path,
+    source_cache_path: &Path,
+    build_target_dir: Option<&Path>,
+    log_path: &Path,
+) -> Result<StagedPlugin, RtpmError> {
+    // The artifacts are built from the manifest head, a pinned plugin or a locked commit is built from its source.
+    if source.locked_artifact.is_some()
+        || (!source.build_from_source && source.pin.is_none() && source.commit.is_none())
+    {
+        // Offline, only the artifacts stored in a local repository are usable.
+        let selected_artifact: Option<(&str, &PluginArtifact)> = select_artifact(plugin_manifest)
+            .filter(|(_, artifact)| {
+                !is_offline()
+                    || (artifact.url.is_none()
+                        && matches!(source.artifact_base, Some(ArtifactBase::Directory(_))))
+            });
+        if let Some(locked_artifact) = &source.locked_artifact {
+            let (_, artifact): (&str, &PluginArtifact) = selected_artifact.ok_or_else(|| {
+                RtpmError::NotFound(format!(
+                    "The locked artifact of the plugin {} is no longer available for this platform.",
+                    plugin_manifest.id
+                ))
+            })?;
+            if !artifact.sha256.eq_ignore_ascii_case(locked_artifact) {
+                return Err(RtpmError::ChecksumMismatch {
+                    subject: format!("the locked artifact of the plugin {}", plugin_manifest.id),
+                    expected: locked_artifact.clone(),
+                    actual: artifact.sha256.to_lowercase(),
+                });
+            }
+        }
+        if let Some((target, artifact)) = selected_artifact {
+            println!(
+                ":: {}",
+                format!("Downloading the prebuilt artifact for {}...", target).green()
+            );
+            let shared_lib_path: PathBuf = fetch_artifact(
+                artifact,
+                source.artifact_base.as_ref(),
+                &staging_path.join("target").join("release"),
+            )?;
+            println!(":: {}", "Artifact downloaded and verified!".green());
+            return Ok(StagedPlugin {
+                shared_lib_relative_path: shared_lib_path
+                    .strip_prefix(staging_path)
+                    .map_or_else(|_| shared_lib_path.clone(), Path::to_path_buf),
+                commit: None,
+                branch: None,
+                artifact: Some(artifact.sha256.to_lowercase()),
+            });
+        }
+    }
+    if is_offline() {
+        if !source_cache_path.exists() {
+            return Err(RtpmError::Offline(format!(
+                "Installing the plugin {} without a cached source",
+                plugin_manifest.id
+            )));
+        }
         println!(
             ":: {}",
-            format!(
-                "The Rtop config file: {} does not exist, you must launch Rtop before using RtopPluginManager.",
-                rtop_config_path.into_os_string().into_string().unwrap()
-            )
-                .red()
+            "Restoring the source of the plugin from the local cache...".green()
         );
+        restore_source_cache(source_cache_path, staging_path)?;
+    } else {
+        clone(&source.url, staging_path)?;
+        if let Err(error) = store_source_cache(staging_path, source_cache_path) {
+            println!(
+                ":: {}",
+                format!("Unable to cache the source of the plugin: {}", error).yellow()
+            );
+        }
+    }
+    let branch: String = if let Some(PluginPin::Branch(branch)) = &source.pin {
+        branch.clone()
+    } else {
+        default_branch(staging_path)?
+    };
+    if let Some(commit) = &source.commit {
         println!(
             ":: {}",
-            "Cleaning the previously installed plugin...".green()
+            format!("Checking out the locked commit {}...", commit).green()
         );
-        std::fs::remove_dir_all(plugin_repository_path).unwrap();
-        println!(":: {}", "Cleaning finished, program exit...".green());
-        std::process::exit(0);
-    }
-    let paths: ReadDir =
-        std::fs::read_dir(plugin_repository_path.join("target").join("release")).unwrap();
-    let mut file_path: String = String::new();
-    for path in paths {
-        let path_un = path.unwrap().path();
-        let extension = path_un.extension();
-        if let Some(extension_name) = extension {
-            if vec!["dll", "so"].contains(&extension_name.to_str().unwrap()) {
-                file_path = path_un.into_os_string().into_string().unwrap();
-            }
-        }
+        checkout_commit(staging_path, commit)?;
+    } else if let Some(pin) = &source.pin {
+        println!(":: {}", format!("Checking out the {}...", pin).green());
+        checkout_pin(staging_path, pin)?;
+    } else if let Some(expected) = &source.expected_commit {
+        println!(
+            ":: {}",
+            format!(
+                "Checking out the commit {} pinned by the repository...",
+                expected
+            )
+            .green()
+        );
+        checkout_expected_commit(staging_path, &plugin_manifest.id, expected)?;
     }
+    let commit: String = if let Some(expected) = &source.expected_commit {
+        verify_commit(staging_path, &plugin_manifest.id, expected)?
+    } else {
+        head_commit(staging_path)?
+    };
+    println!(
+        ":: {}",
+        "Launching the compilation of the plugin...\n".green()
+    );
+    let shared_lib_path: PathBuf = build_plugin(
+        staging_path,
+        build_target_dir,
+        &source.build_options,
+        None,
+        log_path,
+    )?;
+    println!("\n:: {}", "Plugin compiled!".green());
 
-    let mut rtop_config: RtopConfig = read_json_file(&rtop_config_path);
+    Ok(StagedPlugin {
+        shared_lib_relative_path: shared_lib_path
+            .strip_prefix(staging_path)
+            .map_or_else(|_| shared_lib_path.clone(), Path::to_path_buf),
+        commit: Some(commit),
+        branch: Some(branch),
+        artifact: None,
+    })
+}
+
+fn link_plugin(
+    element: RTPMConfigPluginElement,
+    shared_lib_path: &Path,
+    paths: &Paths,
+) -> Result<(), RtpmError> {
+    let rtop_config_path: PathBuf = paths.rtop_config();
+    let rtpm_config_path: PathBuf = paths.rtpm_config();
+    let previous_rtop_config: RtopConfig = read_json_file(&rtop_config_path)?;
+    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path)?;
+
+    println!(":: {}", "Linking plugin to Rtop...".green());
+    let mut rtop_config: RtopConfig = previous_rtop_config.clone();
+    rtop_config.plugins.retain(|r| r.name != element.id);
     rtop_config.plugins.push(RtopConfigPlugins {
-        name: plugin_manifest.id.clone(),
-        path: file_path,
+        name: element.id.clone(),
+        path: shared_lib_path.to_string_lossy().into_owned(),
     });
-    save_json_to_file(&rtop_config, rtop_config_path);
+    save_json_to_file(&rtop_config, rtop_config_path.clone())?;
     println!(":: {}", "Plugin linked to Rtop!".green());
+
     println!(":: {}", "Linking plugin to RTPM...".green());
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
-    rtpm_config.plugins.push(RTPMConfigPluginElement {
-        id: plugin_manifest.id.clone(),
-        name: plugin_manifest.name.clone(),
-        version: plugin_manifest.version,
-        repo: plugin_manifest.url,
-        plugin_type,
-    });
-    save_json_to_file(&rtpm_config, rtpm_config_path);
+    rtpm_config.plugins.retain(|r| r.id != element.id);
+    rtpm_config.plugins.push(element);
+    if let Err(error) = save_json_to_file(&rtpm_config, rtpm_config_path) {
+        println!(":: {}", "Restoring the Rtop config...".green());
+        save_json_to_file(&previous_rtop_config, rtop_config_path)?;
+        return Err(error);
+    }
     println!(":: {}", "Plugin linked to RTPM!".green());
-    println!(":: {}", format!("The plugin {} is now installed! You can execute rtpm -Ip {} to get info about this plugin.", plugin_manifest.name, plugin_manifest.id).green());
-    true
+    Ok(())
+}
+
+// Shallow clone used to read a remote manifest, removed when dropped.
+pub(crate) struct ManifestCheckout(PathBuf);
+
+impl Drop for ManifestCheckout {
+    fn drop(&mut self) {
+        drop(std::fs::remove_dir_all(&self.0));
+    }
+}
+
+pub(crate) struct RemoteManifest {
+    pub(crate) manifest: PluginManifest,
+    pub(crate) manifest_hash: String,
+    pub(crate) artifact_base: ArtifactBase,
+    pub(crate) checkout: Option<ManifestCheckout>,
+}
+
+// Any git URL works, the raw URL of the known hosts only avoids the clone.
+pub(crate) fn fetch_remote_manifest(
+    plugin: &str,
+    reference: Option<&str>,
+    paths: &Paths,
+) -> Result<Option<RemoteManifest>, RtpmError> {
+    if let Some((manifest, manifest_hash, raw_url)) = fetch_raw_manifest(plugin, reference) {
+        return Ok(Some(RemoteManifest {
+            manifest,
+            manifest_hash,
+            artifact_base: ArtifactBase::Url(raw_url),
+            checkout: None,
+        }));
+    }
+
+    let checkout_path: PathBuf = paths
+        .staging()
+        .join(format!("manifest-{}", &sha256_hex(plugin.as_bytes())[..16]));
+    if checkout_path.exists() {
+        std::fs::remove_dir_all(&checkout_path)?;
+    }
+    std::fs::create_dir_all(paths.staging())?;
+    shallow_clone(plugin, reference, &checkout_path)?;
+    let checkout: ManifestCheckout = ManifestCheckout(checkout_path.clone());
+
+    let manifest_content: Vec<u8> =
+        if let Ok(content) = std::fs::read(checkout_path.join("manifest.json")) {
+            content
+        } else {
+            println!(
+                ":: {}",
+                format!(
+                    "The repository {} doesn't contain a manifest.json file.",
+                    plugin
+                )
+                .red()
+                .bold()
+            );
+            return Ok(None);
+        };
+    if let Ok(manifest) = serde_json::from_slice::<PluginManifest>(&manifest_content) {
+        Ok(Some(RemoteManifest {
+            manifest,
+            manifest_hash: sha256_hex(&manifest_content),
+            artifact_base: ArtifactBase::Directory(checkout_path),
+            checkout: Some(checkout),
+        }))
+    } else {
+        println!(":: {}", format!("The manifest of the plugin {} is wrong, please contact the author of this plugin to ask him to change it.", plugin).red().bold());
+        Ok(None)
+    }
+}
+
+fn fetch_raw_manifest(
+    plugin: &str,
+    reference: Option<&str>,
+) -> Option<(PluginManifest, String, Url)> {
+    let url: Url = Url::parse(plugin).ok()?;
+    let git_reference: String = if let Some(requested) = reference {
+        requested.to_owned()
+    } else {
+        remote_default_branch(plugin).ok()?
+    };
+    let raw_url: Url = get_raw_url(&url, &git_reference)?;
+    let manifest_url: Url = raw_url.join("manifest.json").ok()?;
+    let response: Response = http_client()
+        .ok()?
+        .get(manifest_url)
+        .send()
+        .ok()
+        .filter(|response| response.status().is_success())?;
+    let manifest_content: Vec<u8> = response.bytes().ok()?.to_vec();
+    let manifest: PluginManifest = serde_json::from_slice(&manifest_content).ok()?;
+    Some((manifest, sha256_hex(&manifest_content), raw_url))
 }
 
-fn install_insecure_plugins(plugins: Vec<String>) {
+fn read_repository_manifest(
+    plugin: &str,
+    repository_path: &Path,
+) -> Result<(PluginManifest, String), RtpmError> {
+    let manifest_path: PathBuf = repository_path
+        .join("plugins")
+        .join(format!("{}.json", plugin));
+    let plugin_manifest: PluginManifest = read_json_file(&manifest_path)?;
+    let manifest_content: Vec<u8> = std::fs::read(&manifest_path)?;
+    Ok((plugin_manifest, sha256_hex(&manifest_content)))
+}
+
+fn install_insecure_plugins(
+    plugins: Vec<String>,
+    build_from_source: bool,
+    build_request: &BuildRequest,
+    paths: &Paths,
+) -> Result<(), RtpmError> {
     println!(
         ":: {}",
         "Be very careful, using plugins that are not in the official Rtop repos can be dangerous. Rtop is not responsible for any damage that may be caused by these plugins.".yellow().bold()
     );
     print!(":: {} ", "Do you really want to continue? (y/n)".purple());
-    if !user_input_choice() {
-        println!(":: {}", "Exiting...".blue());
-        std::process::exit(0);
+    if !user_input_choice()? {
+        return Err(RtpmError::UserAbort);
     }
 
     for plugin in plugins {
@@ -158,35 +543,95 @@ fn install_insecure_plugins(plugins: Vec<String>) {
             format!("Get the manifest for the repo: {}...", plugin).green()
         );
 
-        let url: Url = if let Ok(url) = Url::parse(&plugin) {
-            url
+        let (plugin_url, pin): (String, Option<PluginPin>) = parse_plugin_pin(&plugin)?;
+        let remote_manifest: RemoteManifest = if let Some(remote_manifest) =
+            fetch_remote_manifest(&plugin_url, pin.as_ref().map(PluginPin::reference), paths)?
+        {
+            remote_manifest
         } else {
             continue;
         };
-        let raw_url: Url = if let Some(temp_url) = get_raw_url(&url) {
-            temp_url
-        } else {
-            continue;
+        let plugin_manifest: &PluginManifest = &remote_manifest.manifest;
+        println!(":: {}", "Manifest recovered!".green());
+        install_dependencies(
+            plugin_manifest,
+            build_from_source,
+            build_request.keep_failed,
+            paths,
+        )?;
+        let source: PluginSource = PluginSource {
+            plugin_type: 1,
+            url: plugin_url.clone(),
+            repository: None,
+            manifest_hash: remote_manifest.manifest_hash.clone(),
+            commit: None,
+            pin,
+            expected_commit: None,
+            artifact_base: Some(remote_manifest.artifact_base.clone()),
+            build_from_source: build_from_source || build_request.is_custom(),
+            locked_artifact: None,
+            build_options: build_request.options_for(plugin_manifest),
+            keep_failed: build_request.keep_failed,
         };
-        let manifest_url: Url = raw_url.join("manifest.json").unwrap();
-
-        let manifest_resp = reqwest::blocking::get(manifest_url.clone())
-            .unwrap()
-            .json::<PluginManifest>();
+        install_plugin(plugin_manifest, &source, paths)?;
+    }
+    println!(":: {}", "Exit...".green());
+    Ok(())
+}
 
-        let plugin_manifest: PluginManifest = if let Ok(manifest) = manifest_resp {
-            manifest
-        } else {
-            println!(":: {}", format!("The manifest of the plugin {} is wrong, please contact the author of this plugin to ask him to change it.", plugin).red().bold());
-            continue;
+// Installs the dependencies of the manifest that are missing, returns `false` when none was installed.
+pub(crate) fn install_dependencies(
+    plugin_manifest: &PluginManifest,
+    build_from_source: bool,
+    keep_failed: bool,
+    paths: &Paths,
+) -> Result<bool, RtpmError> {
+    let requirements: Vec<Requirement> = plugin_manifest
+        .dependencies
+        .iter()
+        .map(|(id, range)| Requirement {
+            id: id.clone(),
+            range: range.clone(),
+            required_by: Some(plugin_manifest.id.clone()),
+        })
+        .collect();
+    if requirements.is_empty() {
+        return Ok(false);
+    }
+    println!(":: {}", "Resolving dependencies...".green());
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    let resolved_plugins: Vec<ResolvedPlugin> =
+        resolve_dependencies(&requirements, &rtpm_config, paths)?;
+    print_dependencies(&resolved_plugins, &BTreeMap::new());
+    let installed: bool = !resolved_plugins.is_empty();
+    for resolved_plugin in resolved_plugins {
+        let dependency_source: PluginSource = PluginSource {
+            plugin_type: 0,
+            url: resolved_plugin.manifest.url.clone(),
+            artifact_base: Some(ArtifactBase::Directory(
+                paths.repository(&resolved_plugin.repository),
+            )),
+            repository: Some(resolved_plugin.repository),
+            manifest_hash: resolved_plugin.manifest_hash,
+            commit: None,
+            pin: None,
+            expected_commit: resolved_plugin.expected_commit,
+            build_from_source,
+            locked_artifact: None,
+            build_options: BuildRequest::default().options_for(&resolved_plugin.manifest),
+            keep_failed,
         };
-        println!(":: {}", "Manifest recovered!".green());
-        install_plugin(plugin_manifest, 1);
+        install_plugin(&resolved_plugin.manifest, &dependency_source, paths)?;
     }
-    println!(":: {}", "Exit...".green());
+    Ok(installed)
 }
 
-fn install_plugins(plugins: Vec<String>) {
+fn install_plugins(
+    plugins: Vec<String>,
+    build_from_source: bool,
+    build_request: &BuildRequest,
+    paths: &Paths,
+) -> Result<(), RtpmError> {
     if plugins.len() > 1 {
         println!(
             ":: {}",
@@ -196,66 +641,271 @@ fn install_plugins(plugins: Vec<String>) {
         println!(":: {}", "Starting installation of plugin...".green());
     }
 
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
 
-    for plugin in plugins {
+    let mut pins: BTreeMap<String, Option<PluginPin>> = BTreeMap::new();
+    let mut requirements: Vec<Requirement> = Vec::new();
+    for plugin_spec in plugins {
+        let (plugin, pin): (String, Option<PluginPin>) = parse_plugin_pin(&plugin_spec)?;
         println!(":: {}", format!("Searching plugin {}...", plugin).green());
-        let repository_path_opt: Option<PathBuf> = search_plugin(
-            plugin.as_str(),
-            rtpm_config.clone(),
-            &rtpm_config_path,
-            true,
+        if search_plugin(plugin.as_str(), &rtpm_config, paths, true)?.is_none() {
+            println!(
+                ":: {}",
+                format!("I couldn't find the {} plugin.", plugin).yellow()
+            );
+            continue;
+        }
+        if rtpm_config.plugins.iter().any(|r| r.id == plugin) {
+            println!(
+                ":: {}",
+                format!(
+                    "The plugin {} is already installed! You can use the {} command to update it.",
+                    plugin,
+                    "rtpm -Sud".bold()
+                )
+                .red()
+            );
+            continue;
+        }
+        requirements.push(Requirement {
+            id: plugin.clone(),
+            range: "*".to_owned(),
+            required_by: None,
+        });
+        pins.insert(plugin, pin);
+    }
+    if requirements.is_empty() {
+        return Ok(());
+    }
+
+    println!(":: {}", "Resolving dependencies...".green());
+    let resolved_plugins: Vec<ResolvedPlugin> =
+        resolve_dependencies(&requirements, &rtpm_config, paths)?;
+    print_dependencies(&resolved_plugins, &pins);
+
+    // Dependencies are built with their default options.
+    let default_request: BuildRequest = BuildRequest::default();
+    for resolved_plugin in resolved_plugins {
+        let plugin_build_request: &BuildRequest = if pins.contains_key(&resolved_plugin.id) {
+            build_request
+        } else {
+            &default_request
+        };
+        let source: PluginSource = PluginSource {
+            plugin_type: 0,
+            url: resolved_plugin.manifest.url.clone(),
+            artifact_base: Some(ArtifactBase::Directory(
+                paths.repository(&resolved_plugin.repository),
+            )),
+            repository: Some(resolved_plugin.repository),
+            manifest_hash: resolved_plugin.manifest_hash,
+            commit: None,
+            pin: pins.remove(&resolved_plugin.id).flatten(),
+            expected_commit: resolved_plugin.expected_commit,
+            build_from_source: build_from_source || plugin_build_request.is_custom(),
+            locked_artifact: None,
+            build_options: plugin_build_request.options_for(&resolved_plugin.manifest),
+            keep_failed: build_request.keep_failed,
+        };
+        install_plugin(&resolved_plugin.manifest, &source, paths)?;
+    }
+    // println!(":: {}", "Exit...".green());
+    Ok(())
+}
+
+fn print_dependencies(
+    resolved_plugins: &[ResolvedPlugin],
+    requested: &BTreeMap<String, Option<PluginPin>>,
+) {
+    let dependencies: Vec<String> = resolved_plugins
+        .iter()
+        .filter(|plugin| !requested.contains_key(&plugin.id))
+        .map(|plugin| format!("{} (v{})", plugin.id, plugin.version))
+        .collect();
+    if !dependencies.is_empty() {
+        println!(
+            ":: {}",
+            format!(
+                "The following dependencies will also be installed: {}",
+                dependencies.join(", ")
+            )
+            .green()
         );
+    }
+}
+
+fn install_locked_plugins(
+    plugins: Option<&[String]>,
+    keep_failed: bool,
+    paths: &Paths,
+) -> Result<(), RtpmError> {
+    println!(
+        ":: {}",
+        "Starting installation of the locked plugins...".green()
+    );
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+
+    if let Some(plugins_list) = plugins {
+        for plugin in plugins_list {
+            if !rtpm_config.plugins.iter().any(|r| &r.id == plugin) {
+                println!(
+                    ":: {}",
+                    format!("The plugin {} is not locked in the RTPM config.", plugin).yellow()
+                );
+            }
+        }
+    }
+
+    for element in rtpm_config.plugins.clone() {
+        if let Some(plugins_list) = plugins {
+            if !plugins_list.contains(&element.id) {
+                continue;
+            }
+        }
+        if paths.plugin(&element.id).exists() {
+            println!(
+                ":: {}",
+                format!("The plugin {} is already installed.", element.name).green()
+            );
+            continue;
+        }
+        if element.commit.is_none() && element.artifact.is_none() {
+            println!(
+                ":: {}",
+                format!(
+                    "The plugin {} has no locked commit, use rtpm -S {} to install it.",
+                    element.name, element.id
+                )
+                .yellow()
+            );
+            continue;
+        }
 
-        let repository_path: PathBuf = if let Some(repository_path) = repository_path_opt {
-            repository_path
+        let (plugin_manifest, manifest_hash, artifact_base, _checkout): (
+            PluginManifest,
+            String,
+            ArtifactBase,
+            Option<ManifestCheckout>,
+        ) = if element.plugin_type == 0 {
+            let recorded_repository: Option<PathBuf> = element
+                .repository
+                .as_ref()
+                .map(|repository| paths.repository(repository))
+                .filter(|path| path.exists());
+            let repository_path: PathBuf = if let Some(repository_path) = recorded_repository {
+                repository_path
+            } else if let Some(repository_path) =
+                search_plugin(&element.id, &rtpm_config, paths, false)?
+            {
+                repository_path
+            } else {
+                println!(
+                    ":: {}",
+                    format!("I couldn't find the {} plugin.", element.id).yellow()
+                );
+                continue;
+            };
+            let (manifest, hash): (PluginManifest, String) =
+                read_repository_manifest(&element.id, &repository_path)?;
+            (
+                manifest,
+                hash,
+                ArtifactBase::Directory(repository_path),
+                None,
+            )
+        } else if let Some(remote_manifest) = fetch_remote_manifest(
+            &element.repo,
+            element
+                .commit
+                .as_deref()
+                .or_else(|| element.pin.as_ref().map(PluginPin::reference)),
+            paths,
+        )? {
+            (
+                remote_manifest.manifest,
+                remote_manifest.manifest_hash,
+                remote_manifest.artifact_base,
+                remote_manifest.checkout,
+            )
         } else {
+            continue;
+        };
+        if element.manifest_hash.as_ref() != Some(&manifest_hash) {
             println!(
                 ":: {}",
-                format!("I couldn't find the {} plugin.", plugin).yellow()
+                format!(
+                    "The manifest of the plugin {} has changed since it was locked.",
+                    element.name
+                )
+                .yellow()
             );
-            break;
+        }
+
+        let source: PluginSource = PluginSource {
+            plugin_type: element.plugin_type,
+            url: element.repo.clone(),
+            repository: element.repository.clone(),
+            manifest_hash,
+            commit: element.commit.clone(),
+            pin: element.pin.clone(),
+            expected_commit: None,
+            artifact_base: Some(artifact_base),
+            build_from_source: element.artifact.is_none(),
+            locked_artifact: element.artifact.clone(),
+            build_options: element.build_options.clone(),
+            keep_failed,
         };
-        let plugin_manifest: PluginManifest = read_json_file(
-            &repository_path
-                .join("plugins")
-                .join(format!("{}.json", plugin)),
-        );
-        install_plugin(plugin_manifest, 0);
+        install_plugin(&plugin_manifest, &source, paths)?;
     }
-    // println!(":: {}", "Exit...".green());
+    Ok(())
 }
 
-pub fn install(matches: &ArgMatches) {
-    let must_println: bool = if contain_clap_arg("update", matches) {
-        update_repositories();
+pub fn install(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let jobs: usize = matches
+        .get_one::<u16>("jobs")
+        .map_or_else(default_jobs, |jobs| usize::from(*jobs));
+    let must_println: bool = if contain_clap_arg("update", matches)? {
+        update_repositories(paths, jobs)?;
         true
     } else {
         false
     };
 
-    if contain_clap_arg("upgrade", matches) {
-        update_packages();
-        std::process::exit(0);
+    if contain_clap_arg("upgrade", matches)? {
+        return update_packages(
+            paths,
+            contain_clap_arg("allow-downgrade", matches)?,
+            contain_clap_arg("keep-failed", matches)?,
+            jobs,
+        );
     }
 
-    let plugins: Vec<String> = matches
-        .get_many::<String>("plugins")
-        .unwrap_or_else(|| {
-            std::process::exit(0);
-        })
-        .cloned()
-        .unique()
-        .collect();
+    if contain_clap_arg("locked", matches)? {
+        let plugins: Option<Vec<String>> = matches
+            .get_many::<String>("plugins")
+            .map(|plugins| plugins.cloned().unique().collect());
+        return install_locked_plugins(
+            plugins.as_deref(),
+            contain_clap_arg("keep-failed", matches)?,
+            paths,
+        );
+    }
+
+    let plugins: Vec<String> = if let Some(plugins) = matches.get_many::<String>("plugins") {
+        plugins.cloned().unique().collect()
+    } else {
+        return Ok(());
+    };
 
     if must_println {
         println!();
     }
 
-    if contain_clap_arg("unsecure-git-url", matches) {
-        install_insecure_plugins(plugins);
+    let build_from_source: bool = contain_clap_arg("build-from-source", matches)?;
+    let build_request: BuildRequest = BuildRequest::from_matches(matches)?;
+    if contain_clap_arg("unsecure-git-url", matches)? {
+        install_insecure_plugins(plugins, build_from_source, &build_request, paths)
     } else {
-        install_plugins(plugins);
+        install_plugins(plugins, build_from_source, &build_request, paths)
     }
 }
@@ -0,0 +1,38 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::utils::is_plugin_id;
+use clap::ArgMatches;
+use colored::Colorize;
+use std::path::PathBuf;
+
+pub fn logs(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let plugin: &str = matches
+        .get_one::<String>("plugin")
+        .ok_or_else(|| RtpmError::InvalidInput("You have not filled a plugin.".to_owned()))?;
+    // Failed installs keep their log, the name is only required to stay inside the logs directory.
+    if !is_plugin_id(plugin) {
+        return Err(RtpmError::InvalidInput(format!(
+            "{} is not a valid plugin name.",
+            plugin
+        )));
+    }
+    let log_path: PathBuf = paths.build_log(plugin);
+    if !log_path.exists() {
+        return Err(RtpmError::NotFound(format!(
+            "No build log is available for the plugin {}.",
+            plugin
+        )));
+    }
+    println!(
+        ":: {}\n",
+        format!(
+            "Latest build log of the plugin {} ({}):",
+            plugin,
+            log_path.display()
+        )
+        .green()
+        .bold()
+    );
+    println!("{}", std::fs::read_to_string(&log_path)?);
+    Ok(())
+}
@@ -1,6 +1,9 @@
 pub mod add_repository;
+pub mod cache;
 pub mod infos;
 pub mod install;
+pub mod logs;
+pub mod outdated;
 pub mod remove_repository;
 pub mod search;
 pub mod uninstall;
@@ -0,0 +1,86 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::structs::{PluginManifest, RTPMConfig, VersionChange};
+use crate::util::utils::{compare_versions, contain_clap_arg, read_json_file, search_plugin};
+use clap::ArgMatches;
+use colored::Colorize;
+use std::path::PathBuf;
+
+pub fn outdated(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let allow_downgrade: bool = contain_clap_arg("allow-downgrade", matches)?;
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+
+    let mut outdated_list: String = String::new();
+    for plugin in &rtpm_config.plugins {
+        if plugin.plugin_type != 0 {
+            println!(
+                ":: {}",
+                format!(
+                    "The plugin {} was installed from a git repository, skipping.",
+                    plugin.name
+                )
+                .yellow()
+            );
+            continue;
+        }
+        let recorded_repository: Option<PathBuf> = plugin
+            .repository
+            .as_ref()
+            .map(|repository| paths.repository(repository))
+            .filter(|repository_path| {
+                repository_path
+                    .join("plugins")
+                    .join(format!("{}.json", plugin.id))
+                    .exists()
+            });
+        let repository_path: PathBuf = if let Some(repository_path) = recorded_repository {
+            repository_path
+        } else if let Some(repository_path) =
+            search_plugin(plugin.id.as_str(), &rtpm_config, paths, false)?
+        {
+            repository_path
+        } else {
+            println!(
+                ":: {}",
+                format!(
+                    "The plugin {} doesn't exist or is not available.",
+                    plugin.name
+                )
+                .red()
+            );
+            continue;
+        };
+
+        let plugin_manifest: PluginManifest = read_json_file(
+            &repository_path
+                .join("plugins")
+                .join(format!("{}.json", plugin.id)),
+        )?;
+        let change: VersionChange = compare_versions(&plugin.version, &plugin_manifest.version);
+        let kind: String = match change {
+            VersionChange::UpToDate => continue,
+            VersionChange::Downgrade if !allow_downgrade => {
+                "downgrade, refused without --allow-downgrade".to_owned()
+            }
+            VersionChange::Major => change.to_string().red().bold().to_string(),
+            VersionChange::Minor => change.to_string().yellow().bold().to_string(),
+            _ => change.to_string(),
+        };
+        outdated_list.push_str(
+            format!(
+                "{} {} -> {} ({})\n",
+                plugin.name.yellow().bold(),
+                plugin.version,
+                plugin_manifest.version.bold(),
+                kind
+            )
+            .as_str(),
+        );
+    }
+    if outdated_list.is_empty() {
+        println!("{}", "All plugins are up to date.".green().bold());
+    } else {
+        println!("{}", outdated_list);
+    }
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
 use crate::util::structs::{PluginManifest, RTPMConfig};
 use crate::util::utils::{read_json_file, search_plugin};
 use clap::ArgMatches;
@@ -5,33 +7,26 @@ use colored::Colorize;
 use itertools::Itertools;
 use std::path::PathBuf;
 
-pub fn search(matches: &ArgMatches) {
-    let plugins: Vec<String> = matches
-        .get_many::<String>("plugins")
-        .unwrap_or_else(|| {
-            println!(
-                "{}",
-                "You have not filled in any plugin or repository."
-                    .red()
-                    .bold()
-            );
-            std::process::exit(0);
-        })
-        .cloned()
-        .unique()
-        .collect();
+pub fn search(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let plugins: Vec<String> = if let Some(plugins) = matches.get_many::<String>("plugins") {
+        plugins.cloned().unique().collect()
+    } else {
+        println!(
+            "{}",
+            "You have not filled in any plugin or repository."
+                .red()
+                .bold()
+        );
+        return Ok(());
+    };
 
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
 
     let mut plugins_list: String = String::new();
     for plugin in plugins {
-        let repository_path: PathBuf = if let Some(repository_path) = search_plugin(
-            plugin.as_str(),
-            rtpm_config.clone(),
-            &rtpm_config_path,
-            false,
-        ) {
+        let repository_path: PathBuf = if let Some(repository_path) =
+            search_plugin(plugin.as_str(), &rtpm_config, paths, false)?
+        {
             repository_path
         } else {
             continue;
@@ -41,7 +36,7 @@ pub fn search(matches: &ArgMatches) {
             &repository_path
                 .join("plugins")
                 .join(format!("{}.json", plugin)),
-        );
+        )?;
         plugins_list.push_str(
             format!("{} - v{}\n", plugin_manifest.name, plugin_manifest.version).as_str(),
         );
@@ -51,4 +46,5 @@ pub fn search(matches: &ArgMatches) {
     } else {
         println!("{}", plugins_list);
     }
+    Ok(())
 }
@@ -1,19 +1,54 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
 use crate::util::structs::{RTPMConfig, RTPMConfigPluginElement, RtopConfig};
-use crate::util::utils::{read_json_file, save_json_to_file};
+use crate::util::utils::{contain_clap_arg, read_json_file, save_json_to_file};
 use clap::ArgMatches;
 use colored::Colorize;
 use itertools::Itertools;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub fn uninstall(matches: &ArgMatches) {
-    let plugins: Vec<String> = matches
-        .get_many::<String>("plugins")
-        .unwrap_or_else(|| {
-            std::process::exit(0);
-        })
-        .cloned()
-        .unique()
-        .collect();
+pub fn uninstall(matches: &ArgMatches, paths: &Paths) -> Result<(), RtpmError> {
+    let mut plugins: Vec<String> = if let Some(plugins) = matches.get_many::<String>("plugins") {
+        plugins.cloned().unique().collect()
+    } else {
+        return Ok(());
+    };
+    let cascade: bool = contain_clap_arg("cascade", matches)?;
+
+    let rtpm_config_path: PathBuf = paths.rtpm_config();
+    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path)?;
+    let rtop_config_path: PathBuf = paths.rtop_config();
+    let mut rtop_config: RtopConfig = read_json_file(&rtop_config_path)?;
+
+    let mut index: usize = 0;
+    while index < plugins.len() {
+        let dependents: Vec<String> = rtpm_config
+            .plugins
+            .iter()
+            .filter(|r| r.dependencies.contains_key(&plugins[index]) && !plugins.contains(&r.id))
+            .map(|r| r.id.clone())
+            .collect();
+        if !dependents.is_empty() {
+            if !cascade {
+                return Err(RtpmError::Dependency(format!(
+                    "The plugin {} is required by {}, use --cascade to uninstall them too.",
+                    plugins[index],
+                    dependents.join(", ")
+                )));
+            }
+            println!(
+                ":: {}",
+                format!(
+                    "The plugin {} is required by {}, they will be uninstalled too.",
+                    plugins[index],
+                    dependents.join(", ")
+                )
+                .yellow()
+            );
+            plugins.extend(dependents);
+        }
+        index += 1;
+    }
 
     if plugins.len() == 1 {
         println!(":: {}", "Start uninstalling plugin...\n".green().bold());
@@ -21,13 +56,6 @@ pub fn uninstall(matches: &ArgMatches) {
         println!(":: {}", "Start uninstalling plugins...\n".green().bold());
     }
 
-    let config_dir: PathBuf = dirs::config_dir().unwrap().join("rtop");
-    let rtpm_config_path: PathBuf = config_dir.join("rtpm.json");
-    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
-    let rtop_config_path: PathBuf = config_dir.join("config.json");
-    let mut rtop_config: RtopConfig = read_json_file(&rtop_config_path);
-    let plugins_path: PathBuf = dirs::data_dir().unwrap().join("rtop").join("plugins");
-
     for plugin in plugins {
         let mut config_plugin_element_opt: Option<RTPMConfigPluginElement> = None;
         for installed_plugin in rtpm_config.plugins.clone() {
@@ -48,36 +76,24 @@ pub fn uninstall(matches: &ArgMatches) {
             format!("Uninstalling the plugin {}...", config_plugin_element.name).green()
         );
 
-        let plugin_path: PathBuf = plugins_path.join(config_plugin_element.id.clone());
+        let plugin_path: PathBuf = paths.plugin(&config_plugin_element.id);
 
         println!(":: {}", "Removing plugin folder...".green());
-        std::fs::remove_dir_all(plugin_path.clone()).unwrap();
+        if plugin_path.exists() {
+            std::fs::remove_dir_all(plugin_path.clone())?;
+        }
         println!(":: {}", "Plugin folder removed!".green());
 
         println!(":: {}", "Removing plugin from RTPM config...".green());
-        let rtpm_plugin_index: usize = rtpm_config
+        rtpm_config
             .plugins
-            .iter()
-            .position(|r| r.id == config_plugin_element.id)
-            .unwrap();
-        rtpm_config.plugins.remove(rtpm_plugin_index);
+            .retain(|r| r.id != config_plugin_element.id);
         println!(":: {}", "Plugin removed from RTPM config!".green());
 
         println!(":: {}", "Removing plugin from Rtop config...".green());
-        let rtop_plugin_index: usize = rtop_config
+        rtop_config
             .plugins
-            .iter()
-            .position(|r| {
-                r.path.starts_with(
-                    &plugin_path
-                        .join("target")
-                        .into_os_string()
-                        .into_string()
-                        .unwrap(),
-                )
-            })
-            .unwrap();
-        rtop_config.plugins.remove(rtop_plugin_index);
+            .retain(|r| !Path::new(&r.path).starts_with(&plugin_path));
         println!(":: {}", "Plugin removed from Rtop config!".green());
 
         println!(
@@ -86,8 +102,9 @@ pub fn uninstall(matches: &ArgMatches) {
         );
     }
 
-    save_json_to_file(&rtpm_config, rtpm_config_path);
-    save_json_to_file(&rtop_config, rtop_config_path);
+    save_json_to_file(&rtpm_config, rtpm_config_path)?;
+    save_json_to_file(&rtop_config, rtop_config_path)?;
 
     println!(":: {}", "End of the uninstallation!".green().bold());
+    Ok(())
 }
@@ -1,11 +1,15 @@
 // Code based on the git2-rs example available on the URL: https://github.com/rust-lang/git2-rs/blob/master/examples/clone.rs.
 
+use crate::git::credentials::remote_callbacks;
+use crate::git::revision::checkout_reference;
+use crate::git::shallow::{clone_shallow, is_commit_hash};
+use crate::util::error::RtpmError;
+use crate::util::network::{clone_depth, ensure_reachable, git_proxy_options};
+use crate::util::progress;
 use crate::util::utils::convert_to_readable_unity;
-use colored::Colorize;
 use git2::build::{CheckoutBuilder, RepoBuilder};
 use git2::{FetchOptions, Progress, RemoteCallbacks};
 use std::cell::{RefCell, RefMut};
-use std::io::{self, Write};
 use std::path::Path;
 
 struct State {
@@ -16,47 +20,87 @@ struct State {
 }
 
 fn print(state: &mut State) {
-    let stats: &Progress = state.progress.as_ref().unwrap();
-    let network_pct: usize = (100 * stats.received_objects()) / stats.total_objects();
+    let stats: &Progress = if let Some(stats) = state.progress.as_ref() {
+        stats
+    } else {
+        return;
+    };
+    let network_pct: usize = (100 * stats.received_objects()) / stats.total_objects().max(1);
     if stats.received_objects() == stats.total_objects() {
         if !state.newline {
-            print!("{}\r", " ".repeat(50));
-            println!(":: {}", "Download terminated!".green());
+            progress::notice("Download terminated!");
             state.newline = true;
         }
 
         if state.total != 0 && state.current == state.total {
-            println!(":: {}", "Checkout terminated!".green());
+            progress::notice("Checkout terminated!");
         } else {
-            print!(
-                ":: {}",
-                format!("Checkout {}/{}...\r", state.current, state.total).green(),
-            );
+            progress::update(&format!("Checkout {}/{}...", state.current, state.total));
         }
     } else {
-        print!(
-            ":: {}",
-            format!(
-                "Download {}% - speed: {} - objects: {}/{}...\r",
-                network_pct,
-                convert_to_readable_unity(stats.received_bytes() as f64),
-                stats.received_objects(),
-                stats.total_objects()
-            )
-            .green(),
-        );
+        progress::update(&format!(
+            "Download {}% - speed: {} - objects: {}/{}...",
+            network_pct,
+            convert_to_readable_unity(stats.received_bytes() as f64),
+            stats.received_objects(),
+            stats.total_objects()
+        ));
+    }
+}
+
+// Only used to read a manifest, the history is never needed.
+pub fn shallow_clone(url: &str, reference: Option<&str>, path: &Path) -> Result<(), RtpmError> {
+    ensure_reachable(url)?;
+    if !reference.is_some_and(is_commit_hash) && clone_shallow(url, path, 1, reference)? {
+        return Ok(());
+    }
+    clone(url, path)?;
+    if let Some(revision) = reference {
+        checkout_reference(path, revision)?;
     }
-    io::stdout().flush().unwrap();
+    Ok(())
 }
 
-pub fn clone(url: &str, path: &Path) {
+// Tries each URL in order and returns the one the repository was cloned from.
+pub fn clone_from_mirrors(urls: &[String], path: &Path) -> Result<String, RtpmError> {
+    let mut last_error: Option<RtpmError> = None;
+    for url in urls {
+        if let Err(error) = ensure_reachable(url) {
+            last_error = Some(error);
+            continue;
+        }
+        match clone(url, path) {
+            Ok(()) => return Ok(url.clone()),
+            Err(error) => {
+                progress::warn(&format!(
+                    "Unable to clone {} ({}), trying the next mirror...",
+                    url, error
+                ));
+                if path.exists() {
+                    std::fs::remove_dir_all(path)?;
+                }
+                last_error = Some(error);
+            }
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| RtpmError::InvalidInput("No repository URL to clone.".to_owned())))
+}
+
+pub fn clone(url: &str, path: &Path) -> Result<(), RtpmError> {
+    ensure_reachable(url)?;
+    let depth: u32 = clone_depth();
+    if depth > 0 && clone_shallow(url, path, depth, None)? {
+        progress::notice("Download terminated!");
+        return Ok(());
+    }
     let state: RefCell<State> = RefCell::new(State {
         progress: None,
         total: 0,
         current: 0,
         newline: false,
     });
-    let mut cb: RemoteCallbacks = RemoteCallbacks::new();
+    let mut cb: RemoteCallbacks = remote_callbacks();
     cb.transfer_progress(|stats| {
         let mut state_borrowed = state.borrow_mut();
         state_borrowed.progress = Some(stats.to_owned());
@@ -74,9 +118,10 @@ pub fn clone(url: &str, path: &Path) {
 
     let mut fo = FetchOptions::new();
     fo.remote_callbacks(cb);
+    fo.proxy_options(git_proxy_options(url));
     RepoBuilder::new()
         .fetch_options(fo)
         .with_checkout(co)
-        .clone(url, path)
-        .unwrap();
+        .clone(url, path)?;
+    Ok(())
 }
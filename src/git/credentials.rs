@@ -0,0 +1,215 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::utils::{read_json_file, set_once};
+use git2::{Config, Cred, CredentialType, RemoteCallbacks};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+use url::Url;
+
+// Loaded once by `main`, git2 callbacks can't borrow the paths of the command.
+static CREDENTIALS: OnceLock<CredentialsFile> = OnceLock::new();
+
+#[derive(Debug, Deserialize, Default, PartialEq, Eq)]
+pub struct CredentialsFile {
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostCredentials>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, PartialEq, Eq)]
+pub struct HostCredentials {
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub ssh_key: Option<PathBuf>,
+    #[serde(default)]
+    pub ssh_passphrase: Option<String>,
+}
+
+#[derive(Default)]
+struct Attempts {
+    agent: bool,
+    ssh_keys: usize,
+    token: bool,
+    helper: bool,
+    username: bool,
+    default: bool,
+}
+
+pub fn load_credentials(paths: &Paths) -> Result<(), RtpmError> {
+    let credentials_path: PathBuf = paths.credentials();
+    let credentials: CredentialsFile = if credentials_path.exists() {
+        read_json_file(&credentials_path)?
+    } else {
+        CredentialsFile::default()
+    };
+    set_once(&CREDENTIALS, credentials, "credential")
+}
+
+// Handles `https://host/...`, `ssh://user@host/...` and `user@host:path`.
+pub fn url_host(url: &str) -> Option<String> {
+    if let Ok(parsed) = Url::parse(url) {
+        return parsed.host_str().map(str::to_owned);
+    }
+    let (_, rest): (&str, &str) = url.split_once('@').unwrap_or(("", url));
+    let (host, _): (&str, &str) = rest.split_once(':')?;
+    if host.is_empty() || host.contains('/') {
+        return None;
+    }
+    Some(host.to_owned())
+}
+
+// `RTPM_GIT_TOKEN_<HOST>` wins over the credentials file, `RTPM_GIT_TOKEN` is only used for the hosts of the
+// credentials file without a token. A token is never sent to a host that wasn't configured.
+pub fn host_credentials(credentials: &CredentialsFile, host: &str) -> HostCredentials {
+    let mut host_credentials: HostCredentials =
+        credentials.hosts.get(host).cloned().unwrap_or_default();
+    let variable: String = format!(
+        "RTPM_GIT_TOKEN_{}",
+        host.chars()
+            .map(|c| if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            })
+            .collect::<String>()
+    );
+    if let Ok(token) = std::env::var(variable) {
+        host_credentials.token = Some(token);
+    } else if host_credentials.token.is_none() && credentials.hosts.contains_key(host) {
+        host_credentials.token = std::env::var("RTPM_GIT_TOKEN").ok();
+    }
+    if host_credentials.ssh_key.is_none() {
+        host_credentials.ssh_key = std::env::var_os("RTPM_SSH_KEY").map(PathBuf::from);
+    }
+    host_credentials
+}
+
+fn ssh_keys(host_credentials: &HostCredentials) -> Vec<PathBuf> {
+    let mut keys: Vec<PathBuf> = host_credentials.ssh_key.iter().cloned().collect();
+    if let Some(home) = dirs::home_dir() {
+        for name in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+            let key: PathBuf = home.join(".ssh").join(name);
+            if key.exists() {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+// Each method is tried once: ssh-agent, SSH keys, token, git credential helpers then the default credentials.
+pub fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut attempts: Attempts = Attempts::default();
+    let mut callbacks: RemoteCallbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        let host: String = url_host(url).unwrap_or_default();
+        let host_credentials: HostCredentials = current_host_credentials(&host);
+        let username: &str = host_credentials
+            .username
+            .as_deref()
+            .or(username_from_url)
+            .unwrap_or("git");
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !attempts.agent {
+                attempts.agent = true;
+                return Cred::ssh_key_from_agent(username);
+            }
+            if let Some(key) = ssh_keys(&host_credentials).get(attempts.ssh_keys) {
+                attempts.ssh_keys += 1;
+                return Cred::ssh_key(
+                    username,
+                    None,
+                    key,
+                    host_credentials.ssh_passphrase.as_deref(),
+                );
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(token) = host_credentials.token.as_deref().filter(|_| !attempts.token) {
+                attempts.token = true;
+                return Cred::userpass_plaintext(
+                    host_credentials
+                        .username
+                        .as_deref()
+                        .or(username_from_url)
+                        .unwrap_or("rtpm"),
+                    token,
+                );
+            }
+            if !attempts.helper {
+                attempts.helper = true;
+                if let Ok(credential) = Config::open_default()
+                    .and_then(|config| Cred::credential_helper(&config, url, username_from_url))
+                {
+                    return Ok(credential);
+                }
+            }
+        }
+        if allowed.contains(CredentialType::USERNAME) && !attempts.username {
+            attempts.username = true;
+            return Cred::username(username);
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !attempts.default {
+            attempts.default = true;
+            return Cred::default();
+        }
+        Err(git2::Error::from_str(&format!(
+            "no credentials were accepted by {}, configure them in the rtpm credentials.json file or with the RTPM_GIT_TOKEN_<HOST> and RTPM_SSH_KEY environment variables",
+            if host.is_empty() { url } else { &host }
+        )))
+    });
+    callbacks
+}
+
+fn current_host_credentials(host: &str) -> HostCredentials {
+    CREDENTIALS.get().map_or_else(
+        || host_credentials(&CredentialsFile::default(), host),
+        |credentials| host_credentials(credentials, host),
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// The git command can't use the callbacks: the token goes through a credential helper reading the environment
+// and the SSH key is given to ssh, which can't use a passphrase outside of the agent.
+pub fn git_command_credentials(command: &mut Command, host_credentials: &HostCredentials) {
+    if let Some(token) = &host_credentials.token {
+        command
+            .env(
+                "RTPM_GIT_USERNAME",
+                host_credentials.username.as_deref().unwrap_or("rtpm"),
+            )
+            .env("RTPM_GIT_PASSWORD", token)
+            .args([
+                "-c",
+                "credential.helper=",
+                "-c",
+                "credential.helper=!f() { echo \"username=${RTPM_GIT_USERNAME}\"; echo \"password=${RTPM_GIT_PASSWORD}\"; }; f",
+            ]);
+    }
+    if let Some(key) = &host_credentials.ssh_key {
+        let mut ssh_command: String = format!(
+            "ssh -o BatchMode=yes -o IdentitiesOnly=yes -i {}",
+            shell_quote(&key.to_string_lossy())
+        );
+        if let Some(username) = &host_credentials.username {
+            ssh_command.push_str(&format!(" -l {}", shell_quote(username)));
+        }
+        command.env("GIT_SSH_COMMAND", ssh_command);
+    }
+}
+
+pub fn configure_git_command(command: &mut Command, url: &str) {
+    git_command_credentials(
+        command,
+        &current_host_credentials(&url_host(url).unwrap_or_default()),
+    );
+}
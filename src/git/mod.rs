@@ -1,4 +1,8 @@
 pub mod clone;
+pub mod credentials;
 pub mod pull;
+pub mod revision;
+pub mod shallow;
+pub mod source_cache;
 pub mod update_repositories;
 pub mod updates_packages;
@@ -1,75 +1,120 @@
 // Code based on the git2-rs example available on the URL: https://github.com/rust-lang/git2-rs/blob/master/examples/pull.rs.
 
+use crate::git::credentials::remote_callbacks;
+use crate::git::shallow::fetch_shallow;
+use crate::util::error::RtpmError;
+use crate::util::network::{ensure_reachable, git_proxy_options};
+use crate::util::progress;
 use crate::util::utils::convert_to_readable_unity;
-use colored::Colorize;
 use git2::{
     AnnotatedCommit, Commit, FetchOptions, Index, MergeAnalysis, MergePreference, Progress,
-    Reference, RemoteCallbacks, Repository, Signature, Tree,
+    Reference, Remote, RemoteCallbacks, Repository, Signature, Tree,
 };
-use std::io::{self, Write};
 use std::str;
 
 pub fn do_fetch<'a>(
     repo: &'a Repository,
     refs: &[&str],
-    remote: &'a mut git2::Remote,
-) -> (Result<AnnotatedCommit<'a>, git2::Error>, bool) {
-    let mut cb: RemoteCallbacks = RemoteCallbacks::new();
+    remote: &mut Remote<'_>,
+) -> Result<(AnnotatedCommit<'a>, bool), RtpmError> {
+    ensure_reachable(remote.url().unwrap_or_default())?;
+    if repo.is_shallow() {
+        return fetch_shallow_repository(repo, refs, remote.url().unwrap_or_default());
+    }
+    let mut cb: RemoteCallbacks = remote_callbacks();
 
     cb.transfer_progress(|stats| {
         if stats.received_objects() == stats.total_objects() {
-            print!(
-                ":: {}",
-                format!(
-                    "Resolving deltas {}/{}\r",
-                    stats.indexed_deltas(),
-                    stats.total_deltas()
-                )
-                .green()
-            );
+            progress::update(&format!(
+                "Resolving deltas {}/{}...",
+                stats.indexed_deltas(),
+                stats.total_deltas()
+            ));
         } else if stats.total_objects() > 0 {
             let network_pct: usize = (100 * stats.received_objects()) / stats.total_objects();
-            print!(
-                ":: {}",
-                format!(
-                    "Download {}% - speed: {} - objects: {}/{}...\r",
-                    network_pct,
-                    convert_to_readable_unity(stats.received_bytes() as f64),
-                    stats.received_objects(),
-                    stats.total_objects()
-                )
-                .green(),
-            );
+            progress::update(&format!(
+                "Download {}% - speed: {} - objects: {}/{}...",
+                network_pct,
+                convert_to_readable_unity(stats.received_bytes() as f64),
+                stats.received_objects(),
+                stats.total_objects()
+            ));
         }
-        io::stdout().flush().unwrap();
         true
     });
 
     let mut fo: FetchOptions = FetchOptions::new();
     fo.remote_callbacks(cb);
+    fo.proxy_options(git_proxy_options(remote.url().unwrap_or_default()));
     fo.download_tags(git2::AutotagOption::All);
-    remote.fetch(refs, Some(&mut fo), None).unwrap();
+    remote.fetch(refs, Some(&mut fo), None)?;
 
     let mut updated: bool = true;
     let stats: Progress = remote.stats();
     if stats.total_objects() == 0 {
-        println!(":: {}", "No updates available for this repository.".green());
+        progress::notice("No updates available for this repository.");
         updated = false;
     } else {
-        println!(
-            ":: {}",
-            format!(
-                "Received {}/{} objects for a total {}.",
-                stats.indexed_objects(),
-                stats.total_objects(),
-                convert_to_readable_unity(stats.received_bytes() as f64)
-            )
-            .green()
-        );
+        progress::notice(&format!(
+            "Received {}/{} objects for a total {}.",
+            stats.indexed_objects(),
+            stats.total_objects(),
+            convert_to_readable_unity(stats.received_bytes() as f64)
+        ));
     }
 
-    let fetch_head: Reference = repo.find_reference("FETCH_HEAD").unwrap();
-    (repo.reference_to_annotated_commit(&fetch_head), updated)
+    let fetch_head: Reference = repo.find_reference("FETCH_HEAD")?;
+    Ok((repo.reference_to_annotated_commit(&fetch_head)?, updated))
+}
+
+fn fetch_shallow_repository<'a>(
+    repo: &'a Repository,
+    refs: &[&str],
+    url: &str,
+) -> Result<(AnnotatedCommit<'a>, bool), RtpmError> {
+    fetch_shallow(repo.workdir().unwrap_or_else(|| repo.path()), url, refs)?;
+    let fetch_head: Reference = repo.find_reference("FETCH_HEAD")?;
+    let fetch_commit: AnnotatedCommit = repo.reference_to_annotated_commit(&fetch_head)?;
+    let updated: bool = repo.head().ok().and_then(|head| head.target()) != Some(fetch_commit.id());
+    if updated {
+        progress::notice(&format!("Received the commit {}.", fetch_commit.id()));
+    } else {
+        progress::notice("No updates available for this repository.");
+    }
+    Ok((fetch_commit, updated))
+}
+
+// Fetches from the first reachable URL and points `origin` to it.
+pub fn fetch_from_mirrors<'a>(
+    repo: &'a Repository,
+    refs: &[&str],
+    urls: &[String],
+) -> Result<(AnnotatedCommit<'a>, String), RtpmError> {
+    let mut last_error: Option<RtpmError> = None;
+    for url in urls {
+        if let Err(error) = ensure_reachable(url) {
+            last_error = Some(error);
+            continue;
+        }
+        let mut remote: Remote = repo.remote_anonymous(url)?;
+        match do_fetch(repo, refs, &mut remote) {
+            Ok((fetch_commit, _)) => {
+                if repo.find_remote("origin")?.url() != Some(url.as_str()) {
+                    repo.remote_set_url("origin", url)?;
+                }
+                return Ok((fetch_commit, url.clone()));
+            }
+            Err(error) => {
+                progress::warn(&format!(
+                    "Unable to fetch {} ({}), trying the next mirror...",
+                    url, error
+                ));
+                last_error = Some(error);
+            }
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| RtpmError::InvalidInput("No repository URL to fetch.".to_owned())))
 }
 
 fn fast_forward(
@@ -125,7 +170,20 @@ pub fn do_merge<'a>(
     repo: &'a Repository,
     remote_branch: &str,
     fetch_commit: &AnnotatedCommit<'a>,
-) -> Result<(), git2::Error> {
+) -> Result<(), RtpmError> {
+    // The merge base may be outside a shallow history, the local branch never has its own commits.
+    if repo.is_shallow() {
+        let ref_name: String = format!("refs/heads/{}", remote_branch);
+        repo.reference(
+            &ref_name,
+            fetch_commit.id(),
+            true,
+            &format!("Setting {} to {}", remote_branch, fetch_commit.id()),
+        )?;
+        repo.set_head(&ref_name)?;
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+        return Ok(());
+    }
     let analysis: (MergeAnalysis, MergePreference) = repo.merge_analysis(&[fetch_commit])?;
 
     if analysis.0.is_fast_forward() {
@@ -0,0 +1,152 @@
+use crate::git::credentials::remote_callbacks;
+use crate::git::shallow::{fetch_revision, is_object_id};
+use crate::util::error::RtpmError;
+use crate::util::network::{ensure_reachable, git_proxy_options};
+use crate::util::structs::PluginPin;
+use git2::build::CheckoutBuilder;
+use git2::{
+    Blob, Buf, Commit, Direction, Oid, ProxyOptions, Reference, Remote, Repository, Tree, TreeEntry,
+};
+use std::path::Path;
+
+pub fn head_commit(path: &Path) -> Result<String, RtpmError> {
+    let repo: Repository = Repository::open(path)?;
+    let commit_id: Oid = repo.head()?.peel_to_commit()?.id();
+    Ok(commit_id.to_string())
+}
+
+pub fn read_commit_file(
+    repo: &Repository,
+    commit: Oid,
+    path: &str,
+) -> Result<Option<Vec<u8>>, RtpmError> {
+    let tree: Tree = repo.find_commit(commit)?.tree()?;
+    let entry: TreeEntry = if let Ok(entry) = tree.get_path(Path::new(path)) {
+        entry
+    } else {
+        return Ok(None);
+    };
+    let blob: Blob = repo.find_blob(entry.id())?;
+    Ok(Some(blob.content().to_vec()))
+}
+
+pub fn default_branch(path: &Path) -> Result<String, RtpmError> {
+    let repo: Repository = Repository::open(path)?;
+    if let Ok(reference) = repo.find_reference("refs/remotes/origin/HEAD") {
+        if let Some(branch) = remote_head_branch(&reference) {
+            return Ok(branch);
+        }
+    }
+    let mut remote: Remote = repo.find_remote("origin")?;
+    connected_default_branch(&mut remote)
+}
+
+pub fn remote_default_branch(url: &str) -> Result<String, RtpmError> {
+    let mut remote: Remote = Remote::create_detached(url)?;
+    connected_default_branch(&mut remote)
+}
+
+fn remote_head_branch(reference: &Reference) -> Option<String> {
+    reference
+        .symbolic_target()?
+        .strip_prefix("refs/remotes/origin/")
+        .map(str::to_owned)
+}
+
+fn connected_default_branch(remote: &mut Remote) -> Result<String, RtpmError> {
+    ensure_reachable(remote.url().unwrap_or_default())?;
+    let proxy_options: ProxyOptions = git_proxy_options(remote.url().unwrap_or_default());
+    remote.connect_auth(
+        Direction::Fetch,
+        Some(remote_callbacks()),
+        Some(proxy_options),
+    )?;
+    let branch_ref: Buf = remote.default_branch()?;
+    remote.disconnect()?;
+    let branch_name: &str = branch_ref.as_str().unwrap_or_default();
+    Ok(branch_name
+        .strip_prefix("refs/heads/")
+        .unwrap_or(branch_name)
+        .to_owned())
+}
+
+pub fn checkout_commit(path: &Path, commit: &str) -> Result<(), RtpmError> {
+    let repo: Repository = Repository::open(path)?;
+    let target: Commit = match repo.revparse_single(commit) {
+        Ok(object) => object,
+        Err(_) if repo.is_shallow() => {
+            fetch_revision(path, commit)?;
+            repo.revparse_single(commit)?
+        }
+        Err(error) => return Err(error.into()),
+    }
+    .peel_to_commit()?;
+    repo.checkout_tree(target.as_object(), Some(CheckoutBuilder::new().force()))?;
+    repo.set_head_detached(target.id())?;
+    Ok(())
+}
+
+// Checks out the commit pinned by a repository and verifies that HEAD is really on it.
+pub fn checkout_expected_commit(
+    path: &Path,
+    plugin_id: &str,
+    expected: &str,
+) -> Result<String, RtpmError> {
+    match checkout_commit(path, expected) {
+        Err(RtpmError::Git(_)) => Err(RtpmError::ChecksumMismatch {
+            subject: format!("the source of the plugin {}", plugin_id),
+            expected: expected.to_owned(),
+            actual: "a repository without this commit".to_owned(),
+        }),
+        result => result,
+    }?;
+    verify_commit(path, plugin_id, expected)
+}
+
+// Both commits must be full object ids.
+pub fn same_commit(commit: &str, expected: &str) -> bool {
+    is_object_id(commit)
+        && is_object_id(expected)
+        && Oid::from_str(commit).ok() == Oid::from_str(expected).ok()
+}
+
+pub fn verify_commit(path: &Path, plugin_id: &str, expected: &str) -> Result<String, RtpmError> {
+    let commit: String = head_commit(path)?;
+    if !same_commit(&commit, expected) {
+        return Err(RtpmError::ChecksumMismatch {
+            subject: format!("the source of the plugin {}", plugin_id),
+            expected: expected.to_owned(),
+            actual: commit,
+        });
+    }
+    Ok(commit)
+}
+
+// Accepts a commit, a tag or a branch of `origin`.
+pub fn checkout_reference(path: &Path, reference: &str) -> Result<(), RtpmError> {
+    let remote_branch: String = format!("refs/remotes/origin/{}", reference);
+    if Repository::open(path)?
+        .revparse_single(&remote_branch)
+        .is_ok()
+    {
+        return checkout_commit(path, &remote_branch);
+    }
+    checkout_commit(path, reference)
+}
+
+pub fn checkout_pin(path: &Path, pin: &PluginPin) -> Result<(), RtpmError> {
+    match pin {
+        PluginPin::Tag(tag) => checkout_commit(path, &format!("refs/tags/{}", tag)),
+        PluginPin::Commit(commit) => checkout_commit(path, commit),
+        PluginPin::Branch(branch) => {
+            let repo: Repository = Repository::open(path)?;
+            let target: Commit = repo
+                .revparse_single(&format!("refs/remotes/origin/{}", branch))?
+                .peel_to_commit()?;
+            repo.branch(branch, &target, true)?;
+            repo.set_head(&format!("refs/heads/{}", branch))?;
+            repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// libgit2 can neither create nor fetch shallow repositories, these operations use the git command when it is available.
+
+use crate::git::credentials::configure_git_command;
+use crate::util::error::RtpmError;
+use crate::util::network::{clone_depth, git_ca_bundle, proxy_for};
+use colored::Colorize;
+use git2::Repository;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+// Uses the same credentials, proxy and certificates as the git2 operations.
+fn git_command(url: &str) -> Command {
+    let mut command: Command = Command::new("git");
+    command.env("GIT_TERMINAL_PROMPT", "0").stdin(Stdio::null());
+    if let Some(proxy) = proxy_for(url) {
+        command.env("http_proxy", proxy).env("https_proxy", proxy);
+    }
+    if let Some(bundle) = git_ca_bundle() {
+        command
+            .arg("-c")
+            .arg(format!("http.sslCAInfo={}", bundle.display()));
+    }
+    configure_git_command(&mut command, url);
+    command
+}
+
+fn run(mut command: Command) -> Result<(), RtpmError> {
+    let output: Output = command.output().map_err(|error| {
+        if error.kind() == ErrorKind::NotFound {
+            RtpmError::NotFound("The git command is required to update shallow clones, install git or set the network clone_depth to 0 and reinstall the repository or plugin.".to_owned())
+        } else {
+            error.into()
+        }
+    })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(RtpmError::Git(git2::Error::from_str(&format!(
+        "git exited with {} ({})",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))))
+}
+
+// URLs and references come from manifests and repository indexes, one starting with `-` would be read as an option.
+pub fn check_git_argument(value: &str) -> Result<(), RtpmError> {
+    if value.starts_with('-') {
+        return Err(RtpmError::InvalidInput(format!(
+            "{} is not a valid git URL or reference.",
+            value
+        )));
+    }
+    Ok(())
+}
+
+// A full object id, the repository entries can't pin a commit with a prefix matching several commits.
+pub fn is_object_id(revision: &str) -> bool {
+    revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub fn is_commit_hash(revision: &str) -> bool {
+    revision.len() >= 7 && revision.len() <= 40 && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Returns `false` when git is missing or the clone failed, the caller then does a full clone.
+pub fn clone_shallow(
+    url: &str,
+    path: &Path,
+    depth: u32,
+    branch: Option<&str>,
+) -> Result<bool, RtpmError> {
+    check_git_argument(url)?;
+    let mut command: Command = git_command(url);
+    command.args(["clone", "--quiet", "--depth", &depth.to_string()]);
+    if let Some(reference) = branch {
+        check_git_argument(reference)?;
+        command.args(["--branch", reference]);
+    } else {
+        command.arg("--no-single-branch");
+    }
+    command.arg("--").arg(url).arg(path).stderr(Stdio::null());
+    if command.status().is_ok_and(|status| status.success()) {
+        return Ok(true);
+    }
+    if path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+    Ok(false)
+}
+
+pub fn fetch_shallow(path: &Path, url: &str, refs: &[&str]) -> Result<(), RtpmError> {
+    check_git_argument(url)?;
+    for reference in refs {
+        check_git_argument(reference)?;
+    }
+    let mut command: Command = git_command(url);
+    command
+        .arg("-C")
+        .arg(path)
+        .args([
+            "fetch",
+            "--quiet",
+            "--depth",
+            &clone_depth().max(1).to_string(),
+            "--",
+            url,
+        ])
+        .args(refs);
+    run(command)
+}
+
+// Fetches a revision missing from the shallow history, the whole history is fetched when the remote refuses it.
+pub fn fetch_revision(path: &Path, revision: &str) -> Result<(), RtpmError> {
+    let repo: Repository = Repository::open(path)?;
+    let url: String = repo
+        .find_remote("origin")?
+        .url()
+        .unwrap_or_default()
+        .to_owned();
+    println!(
+        ":: {}",
+        format!(
+            "The revision {} is not in the shallow history, fetching it...",
+            revision
+        )
+        .green()
+    );
+    let refspec: String = if revision.starts_with("refs/") {
+        format!("+{}:{}", revision, revision)
+    } else if is_commit_hash(revision) {
+        revision.to_owned()
+    } else {
+        format!("+refs/tags/{}:refs/tags/{}", revision, revision)
+    };
+    if fetch_shallow(path, &url, &[&refspec]).is_ok() {
+        return Ok(());
+    }
+    let mut command: Command = git_command(&url);
+    command
+        .arg("-C")
+        .arg(path)
+        .args(["fetch", "--quiet", "--unshallow", "--tags", "origin"]);
+    run(command)
+}
@@ -0,0 +1,20 @@
+use crate::util::error::RtpmError;
+use crate::util::utils::copy_directory;
+use git2::build::CheckoutBuilder;
+use git2::Repository;
+use std::path::Path;
+
+// The cache keeps the `.git` directory of the last fresh clone, with its remote branches and tags.
+pub fn store_source_cache(repository_path: &Path, cache_path: &Path) -> Result<(), RtpmError> {
+    if cache_path.exists() {
+        std::fs::remove_dir_all(cache_path)?;
+    }
+    copy_directory(&repository_path.join(".git"), cache_path)
+}
+
+pub fn restore_source_cache(cache_path: &Path, repository_path: &Path) -> Result<(), RtpmError> {
+    copy_directory(cache_path, &repository_path.join(".git"))?;
+    let repo: Repository = Repository::open(repository_path)?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    Ok(())
+}
@@ -1,18 +1,35 @@
-use crate::git::clone::clone;
-use crate::git::pull::{do_fetch, do_merge};
-use crate::util::structs::{RTPMConfig, RepositoryManifest};
+use crate::git::clone::{clone, clone_from_mirrors};
+use crate::git::pull::{do_merge, fetch_from_mirrors};
+use crate::git::revision::{default_branch, read_commit_file};
+use crate::util::error::RtpmError;
+use crate::util::network::ensure_online;
+use crate::util::paths::Paths;
+use crate::util::progress::run_parallel;
+use crate::util::signature::{
+    check_cloned_repository_signature, check_repository_signature, INDEX_FILE, INDEX_SIGNATURE_FILE,
+};
+use crate::util::structs::{RTPMConfig, RTPMConfigRepositoryElement, RepositoryManifest};
 use crate::util::utils::{read_json_file, save_json_to_file};
 use colored::Colorize;
-use git2::{AnnotatedCommit, Remote, Repository};
+use git2::{AnnotatedCommit, Oid, Repository};
 use std::fs::DirEntry;
 use std::path::PathBuf;
 
-pub fn update_repositories() {
+struct RepositoryUpdate {
+    folder_name: String,
+    path: PathBuf,
+    manifest: RepositoryManifest,
+    branch: String,
+    mirrors: Vec<String>,
+}
+
+pub fn update_repositories(paths: &Paths, jobs: usize) -> Result<(), RtpmError> {
+    ensure_online("Updating the plugin repositories")?;
     println!(
         ":: {}",
         "Update of all Rtop plugin repositories...\n".green().bold()
     );
-    let repositories_path: PathBuf = dirs::data_dir().unwrap().join("rtop").join("repositories");
+    let repositories_path: PathBuf = paths.repositories();
     let mut must_update_rtop: bool = true;
     if !repositories_path.join("rtop").exists() {
         println!(
@@ -26,75 +43,175 @@ pub fn update_repositories() {
         clone(
             "https://github.com/RtopRS/PluginsRepository/",
             &repositories_path.join("rtop"),
-        );
+        )?;
     }
 
-    let rtpm_config_path: PathBuf = dirs::config_dir().unwrap().join("rtop").join("rtpm.json");
-    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
+    let rtpm_config_path: PathBuf = paths.rtpm_config();
+    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path)?;
+    if !must_update_rtop {
+        let rtop_path: PathBuf = repositories_path.join("rtop");
+        let details: &mut RTPMConfigRepositoryElement = rtpm_config
+            .repository_details
+            .entry("rtop".to_owned())
+            .or_default();
+        if let Err(error) = check_cloned_repository_signature("rtop", &rtop_path, details) {
+            std::fs::remove_dir_all(rtop_path)?;
+            return Err(error);
+        }
+        details.branch = Some(default_branch(&rtop_path)?);
+    }
 
-    for repository_result in std::fs::read_dir(repositories_path).unwrap() {
-        let repository: &DirEntry = repository_result.as_ref().unwrap();
+    let mut repositories: Vec<(String, RepositoryUpdate)> = Vec::new();
+    for repository_result in std::fs::read_dir(repositories_path)? {
+        let repository: DirEntry = repository_result?;
 
-        let folder_name: String = repository.file_name().into_string().unwrap();
+        let folder_name: String = repository.file_name().to_string_lossy().into_owned();
         if !must_update_rtop && repository.file_name() == "rtop" {
             continue;
         }
         let repo_manifest: RepositoryManifest =
-            read_json_file(&repository.path().join("manifest.json"));
-        println!(
-            ":: {}",
-            format!(
-                "Updating the repository: {} ({})...",
-                repo_manifest.name.bold(),
-                repo_manifest.url
-            )
-            .green()
-        );
+            read_json_file(&repository.path().join("manifest.json"))?;
 
         if !rtpm_config.repositories.contains(&folder_name) {
             println!(
                 ":: {}",
-                "The repository is not present in the config, this one has been added."
-                    .yellow()
-                    .bold()
+                format!(
+                    "The repository {} is not present in the config, this one has been added.",
+                    folder_name
+                )
+                .yellow()
+                .bold()
             );
-            rtpm_config.repositories.push(folder_name);
+            rtpm_config.repositories.push(folder_name.clone());
         }
 
-        let repo: Repository = Repository::open(repository.path()).unwrap();
-        let mut remote: Remote = repo.find_remote("origin").unwrap();
-        let fetch = do_fetch(&repo, &["main"], &mut remote).0;
-        let fetch_commit: AnnotatedCommit = if let Err(error) = fetch {
+        let branch: String = if let Some(branch) = rtpm_config
+            .repository_details
+            .get(&folder_name)
+            .and_then(|details| details.branch.clone())
+        {
+            branch
+        } else {
+            match default_branch(&repository.path()) {
+                Ok(branch) => {
+                    rtpm_config
+                        .repository_details
+                        .entry(folder_name.clone())
+                        .or_default()
+                        .branch = Some(branch.clone());
+                    branch
+                }
+                Err(error) => {
+                    println!(
+                        ":: {}",
+                        format!(
+                            "Unable to find the default branch of the repository {}: {}",
+                            folder_name, error
+                        )
+                        .red()
+                        .bold()
+                    );
+                    continue;
+                }
+            }
+        };
+
+        let repo: Repository = Repository::open(repository.path())?;
+        let mirrors: Vec<String> = repository_mirrors(&repo, &repo_manifest)?;
+        repositories.push((
+            repo_manifest.name.clone(),
+            RepositoryUpdate {
+                folder_name,
+                path: repository.path(),
+                manifest: repo_manifest,
+                branch,
+                mirrors,
+            },
+        ));
+    }
+
+    println!(":: {}", "Fetching the repositories...".green());
+    let fetches: Vec<Result<(Oid, String), RtpmError>> =
+        run_parallel(&repositories, jobs, |update: &RepositoryUpdate| {
+            let repo: Repository = Repository::open(&update.path)?;
+            let (fetch_commit, mirror): (AnnotatedCommit, String) =
+                fetch_from_mirrors(&repo, &[update.branch.as_str()], &update.mirrors)?;
+            Ok((fetch_commit.id(), mirror))
+        });
+
+    for ((_, update), fetch_result) in repositories.iter().zip(fetches) {
+        // Fetch errors are already shown by the progress display.
+        let (fetch_commit_id, mirror): (Oid, String) = match fetch_result {
+            Ok(fetch) => fetch,
+            Err(_) => continue,
+        };
+        rtpm_config
+            .repository_details
+            .entry(update.folder_name.clone())
+            .or_default()
+            .mirror = Some(mirror);
+        let repo: Repository = Repository::open(&update.path)?;
+        if let Err(error) = verify_fetched_index(
+            &repo,
+            fetch_commit_id,
+            &update.folder_name,
+            rtpm_config
+                .repository_details
+                .entry(update.folder_name.clone())
+                .or_default(),
+        ) {
             println!(
                 ":: {}",
-                format!(
-                    "An error occurred while fetching the repository: {}",
-                    error.message()
-                )
-                .red()
-                .bold()
+                format!("{} The repository was not updated.", error)
+                    .red()
+                    .bold()
             );
             continue;
-        } else {
-            fetch.unwrap()
-        };
-        if let Err(error) = do_merge(&repo, "main", &fetch_commit) {
-            if error.message() == "no merge base found" {
+        }
+        let fetch_commit: AnnotatedCommit = repo.find_annotated_commit(fetch_commit_id)?;
+        if let Err(error) = do_merge(&repo, &update.branch, &fetch_commit) {
+            if matches!(&error, RtpmError::Git(git_error) if git_error.message() == "no merge base found")
+            {
                 println!(
                     ":: {}",
-                    "Unable to update the repository, re-installation..."
+                    format!(
+                        "Unable to update the repository {}, re-installation...",
+                        update.manifest.name
+                    )
+                    .red()
+                    .bold()
+                );
+                drop(fetch_commit);
+                drop(repo);
+                std::fs::remove_dir_all(&update.path)?;
+                clone_from_mirrors(&update.mirrors, &update.path)?;
+                if let Err(signature_error) = check_cloned_repository_signature(
+                    &update.folder_name,
+                    &update.path,
+                    rtpm_config
+                        .repository_details
+                        .entry(update.folder_name.clone())
+                        .or_default(),
+                ) {
+                    std::fs::remove_dir_all(&update.path)?;
+                    println!(
+                        ":: {}",
+                        format!(
+                            "{} The repository was removed, add it again once its index is signed.",
+                            signature_error
+                        )
                         .red()
                         .bold()
-                );
-                std::fs::remove_dir_all(repository.path()).unwrap();
-                clone(&repo_manifest.url, &repository.path());
+                    );
+                    continue;
+                }
                 println!(":: {}", "Repository re-installed!".green());
             } else {
                 println!(
                     ":: {}",
                     format!(
-                        "An error occurred while merging the repository: {}",
-                        error.message()
+                        "An error occurred while merging the repository {}: {}",
+                        update.manifest.name, error
                     )
                     .red()
                     .bold()
@@ -105,15 +222,16 @@ pub fn update_repositories() {
         println!(
             ":: {}",
             format!(
-                "Update of the repository: {} ({}) is terminated!\n",
-                repo_manifest.name.bold(),
-                repo_manifest.url
+                "Update of the repository: {} ({}) is terminated!",
+                update.manifest.name.bold(),
+                update.manifest.url
             )
             .green()
         );
     }
+    println!();
 
-    save_json_to_file(&rtpm_config, rtpm_config_path);
+    save_json_to_file(&rtpm_config, rtpm_config_path)?;
 
     println!(
         ":: {}",
@@ -121,4 +239,41 @@ pub fn update_repositories() {
             .green()
             .bold()
     );
+    Ok(())
+}
+
+// The manifest URLs come first, the current `origin` is kept as a last resort.
+fn repository_mirrors(
+    repo: &Repository,
+    repo_manifest: &RepositoryManifest,
+) -> Result<Vec<String>, RtpmError> {
+    let mut mirrors: Vec<String> = repo_manifest.mirrors();
+    if let Some(origin) = repo.find_remote("origin")?.url() {
+        if !mirrors.iter().any(|mirror| mirror == origin) {
+            mirrors.push(origin.to_owned());
+        }
+    }
+    Ok(mirrors)
+}
+
+fn verify_fetched_index(
+    repo: &Repository,
+    commit: Oid,
+    repository: &str,
+    details: &mut RTPMConfigRepositoryElement,
+) -> Result<(), RtpmError> {
+    let manifest: Option<RepositoryManifest> = read_commit_file(repo, commit, "manifest.json")?
+        .and_then(|content| serde_json::from_slice(&content).ok());
+    let index: Vec<u8> = read_commit_file(repo, commit, INDEX_FILE)?.unwrap_or_default();
+    let signature: Option<String> = read_commit_file(repo, commit, INDEX_SIGNATURE_FILE)?
+        .map(|content| String::from_utf8_lossy(&content).into_owned());
+    check_repository_signature(
+        repository,
+        manifest
+            .as_ref()
+            .and_then(|repository_manifest| repository_manifest.public_key.as_deref()),
+        &index,
+        signature.as_deref(),
+        details,
+    )
 }
@@ -1,196 +1,715 @@
+use crate::commands::install::{fetch_remote_manifest, install_dependencies, ManifestCheckout};
 use crate::git::clone::clone;
 use crate::git::pull::{do_fetch, do_merge};
-use crate::util::structs::{PluginManifest, RTPMConfig, RtopConfig};
+use crate::git::revision::{
+    checkout_expected_commit, checkout_pin, default_branch, head_commit, same_commit,
+};
+use crate::util::artifact::{fetch_artifact, select_artifact, ArtifactBase};
+use crate::util::error::RtpmError;
+use crate::util::network::ensure_online;
+use crate::util::paths::Paths;
+use crate::util::progress::{default_jobs, run_parallel};
+use crate::util::signature::signed_repository;
+use crate::util::structs::{
+    BuildOptions, PluginArtifact, PluginManifest, PluginPin, RTPMConfig, RTPMConfigPluginElement,
+    RepositoryPlugin, RepositoryPluginEntry, RtopConfig, VersionChange,
+};
 use crate::util::utils::{
-    build_cargo_project, read_json_file, save_json_to_file, search_plugin, user_input_choice,
+    build_plugin, compare_versions, current_timestamp, detect_rtop_version, keep_failed_build,
+    read_json_file, save_json_to_file, search_plugin, sha256_hex, user_input_choice,
+    verify_repository_entry, verify_rtop_compatibility,
 };
 use colored::Colorize;
-use git2::{AnnotatedCommit, Remote, Repository};
+use git2::{AnnotatedCommit, Object, Oid, Remote, Repository, ResetType};
+use semver::Version;
+use std::ffi::OsStr;
 use std::fs::DirEntry;
 use std::path::{Path, PathBuf};
 
-pub fn update_packages() {
+struct PluginUpdate {
+    id: String,
+    path: PathBuf,
+    manifest: PluginManifest,
+    manifest_path: PathBuf,
+    branch: String,
+    pin: Option<PluginPin>,
+    expected_commit: Option<String>,
+}
+
+struct PluginBuild {
+    id: String,
+    path: PathBuf,
+    manifest: PluginManifest,
+    manifest_path: PathBuf,
+    shared_lib_path: String,
+    shared_lib_temp_path: PathBuf,
+    build_options: BuildOptions,
+}
+
+// The configs are saved after each plugin, a later failure can't leave Rtop pointing to a removed library.
+struct Upgrade<'a> {
+    paths: &'a Paths,
+    rtpm_config: RTPMConfig,
+    rtop_config: RtopConfig,
+    rtop_version: Option<Version>,
+    allow_downgrade: bool,
+    keep_failed: bool,
+    failures: usize,
+}
+
+pub fn update_packages(
+    paths: &Paths,
+    allow_downgrade: bool,
+    keep_failed: bool,
+    jobs: usize,
+) -> Result<(), RtpmError> {
+    ensure_online("Upgrading the plugins")?;
     println!(":: {}", "Update of all Rtop plugins...\n".green().bold());
-    let plugins_path: PathBuf = dirs::data_dir().unwrap().join("rtop").join("plugins");
-    let base_rtop_path: PathBuf = dirs::config_dir().unwrap().join("rtop");
-    let rtop_config_path: PathBuf = base_rtop_path.join("config.json");
-    let mut rtop_config: RtopConfig = read_json_file(&rtop_config_path);
-    let rtpm_config_path: PathBuf = base_rtop_path.join("rtpm.json");
-    let mut rtpm_config: RTPMConfig = read_json_file(&rtpm_config_path);
-
-    for plugin_result in std::fs::read_dir(plugins_path.clone()).unwrap() {
-        let plugin: &DirEntry = plugin_result.as_ref().unwrap();
-        let folder_name: String = plugin.file_name().into_string().unwrap();
-        let plugin_index: usize = if let Some(plugin_index) =
-            rtpm_config.plugins.iter().position(|r| r.id == folder_name)
+    let mut upgrade: Upgrade = Upgrade {
+        paths,
+        rtpm_config: read_json_file(&paths.rtpm_config())?,
+        rtop_config: read_json_file(&paths.rtop_config())?,
+        rtop_version: detect_rtop_version(paths),
+        allow_downgrade,
+        keep_failed,
+        failures: 0,
+    };
+
+    let mut updates: Vec<(String, PluginUpdate)> = Vec::new();
+    for plugin_result in std::fs::read_dir(paths.plugins())? {
+        let plugin: DirEntry = plugin_result?;
+        let folder_name: String = plugin.file_name().to_string_lossy().into_owned();
+        let is_artifact: bool = if let Some(element) = upgrade
+            .rtpm_config
+            .plugins
+            .iter()
+            .find(|r| r.id == folder_name)
         {
-            plugin_index
+            element.artifact.is_some()
         } else {
             continue;
         };
-        let plugin_manifest_path: PathBuf = if rtpm_config.plugins[plugin_index].plugin_type == 0 {
-            if let Some(repository_path) = search_plugin(
-                folder_name.as_str(),
-                rtpm_config.clone(),
-                &rtpm_config_path,
-                false,
-            ) {
-                repository_path
-                    .join("plugins")
-                    .join(format!("{}.json", folder_name))
-            } else {
-                println!(
-                    ":: {}",
-                    format!(
-                        "The plugin {} doesn't exist or is not available.",
-                        folder_name
-                    )
-                    .red()
-                    .bold()
-                );
-                continue;
+        if is_artifact {
+            if let Err(error) = upgrade.update_artifact(&folder_name) {
+                upgrade.fail(&folder_name, &error);
             }
+            continue;
+        }
+        match upgrade.prepare(&folder_name, &plugin.path()) {
+            Ok(Some(update)) => updates.push((update.manifest.name.clone(), update)),
+            Ok(None) => {}
+            Err(error) => upgrade.fail(&folder_name, &error),
+        }
+    }
+    // Keeps the default branches detected while preparing the updates.
+    upgrade.save()?;
+
+    println!(":: {}", "Fetching the plugins...".green());
+    let fetches: Vec<Result<(Oid, bool), RtpmError>> =
+        run_parallel(&updates, jobs, |update: &PluginUpdate| {
+            let git_repository: Repository = Repository::open(&update.path)?;
+            let mut remote: Remote = git_repository.find_remote("origin")?;
+            let (fetch_commit, need_compilation): (AnnotatedCommit, bool) =
+                do_fetch(&git_repository, &[update.branch.as_str()], &mut remote)?;
+            Ok((fetch_commit.id(), need_compilation))
+        });
+
+    let mut builds: Vec<(String, PluginBuild)> = Vec::new();
+    for ((name, update), fetch_result) in updates.into_iter().zip(fetches) {
+        // Fetch errors are already shown by the progress display.
+        let (fetch_commit_id, need_compilation): (Oid, bool) = if let Ok(fetch) = fetch_result {
+            fetch
         } else {
-            plugin.path().join("manifest.json")
+            upgrade.failures += 1;
+            continue;
         };
+        match upgrade.apply_fetch(update, fetch_commit_id, need_compilation) {
+            Ok(Some(build)) => builds.push((name, build)),
+            Ok(None) => {}
+            Err(error) => upgrade.fail(&name, &error),
+        }
+    }
+    println!();
+
+    // Each build gets its share of the CPUs, cargo would otherwise start `jobs` rustc per build.
+    let build_jobs: u32 =
+        u32::try_from(default_jobs() / jobs.clamp(1, builds.len().max(1))).unwrap_or(1);
+    if !builds.is_empty() {
+        println!(":: {}", "Compilation of the updated plugins...".green());
+    }
+    let build_target_dir: Option<PathBuf> = upgrade
+        .rtpm_config
+        .build
+        .shared_target_dir
+        .then(|| paths.build_cache());
+    let results: Vec<Result<PathBuf, RtpmError>> =
+        run_parallel(&builds, jobs, |build: &PluginBuild| {
+            build_plugin(
+                &build.path,
+                build_target_dir.as_deref(),
+                &build.build_options,
+                Some(build_jobs),
+                &paths.build_log(&build.id),
+            )
+        });
 
-        let plugin_manifest: PluginManifest = read_json_file(&plugin_manifest_path);
+    for ((name, build), result) in builds.into_iter().zip(results) {
+        if let Err(error) = upgrade.finish_build(build, result) {
+            upgrade.fail(&name, &error);
+        }
+    }
+    upgrade.save()?;
+    if upgrade.failures > 0 {
         println!(
             ":: {}",
             format!(
-                "Updating the plugin: {} ({})...",
-                plugin_manifest.name.bold(),
-                plugin_manifest.url
+                "Update of the Rtop plugins completed, {} of them could not be updated.",
+                upgrade.failures
             )
-            .green()
+            .yellow()
+            .bold()
+        );
+    } else {
+        println!(
+            ":: {}",
+            "Update of all Rtop plugins completed!".green().bold()
         );
+    }
+    Ok(())
+}
 
-        let git_repository: Repository = Repository::open(plugin.path()).unwrap();
-        let mut remote: Remote = git_repository.find_remote("origin").unwrap();
-        let fetch = do_fetch(&git_repository, &["main"], &mut remote);
-        let need_compilation: bool;
-        let fetch_commit: AnnotatedCommit = if fetch.0.is_err() {
+impl Upgrade<'_> {
+    fn save(&self) -> Result<(), RtpmError> {
+        save_json_to_file(&self.rtop_config, self.paths.rtop_config())?;
+        save_json_to_file(&self.rtpm_config, self.paths.rtpm_config())
+    }
+
+    fn fail(&mut self, plugin: &str, error: &RtpmError) {
+        self.failures += 1;
+        println!(
+            ":: {}\n",
+            format!(
+                "An error occurred while updating the plugin {}: {}",
+                plugin, error
+            )
+            .red()
+            .bold()
+        );
+    }
+
+    // The new version must not conflict with an installed plugin and its new dependencies are installed first.
+    fn install_new_dependencies(
+        &mut self,
+        plugin_manifest: &PluginManifest,
+    ) -> Result<(), RtpmError> {
+        if let Some(conflicting) = self.rtpm_config.plugins.iter().find(|r| {
+            r.id != plugin_manifest.id
+                && (plugin_manifest.conflicts.contains(&r.id)
+                    || r.conflicts.contains(&plugin_manifest.id))
+        }) {
+            return Err(RtpmError::Dependency(format!(
+                "The new version of the plugin {} conflicts with the installed plugin {}.",
+                plugin_manifest.id, conflicting.id
+            )));
+        }
+        if plugin_manifest.dependencies.is_empty() {
+            return Ok(());
+        }
+        self.save()?;
+        if install_dependencies(plugin_manifest, false, self.keep_failed, self.paths)? {
+            self.rtpm_config = read_json_file(&self.paths.rtpm_config())?;
+            self.rtop_config = read_json_file(&self.paths.rtop_config())?;
+        }
+        Ok(())
+    }
+
+    fn prepare(
+        &mut self,
+        folder_name: &str,
+        plugin_path: &Path,
+    ) -> Result<Option<PluginUpdate>, RtpmError> {
+        let plugin_index: usize = if let Some(plugin_index) = self
+            .rtpm_config
+            .plugins
+            .iter()
+            .position(|r| r.id == folder_name)
+        {
+            plugin_index
+        } else {
+            return Ok(None);
+        };
+        let mut repository_entry: Option<RepositoryPluginEntry> = None;
+        let mut repository_name: String = String::new();
+        let plugin_manifest_path: PathBuf =
+            if self.rtpm_config.plugins[plugin_index].plugin_type == 0 {
+                if let Some(repository_path) =
+                    search_plugin(folder_name, &self.rtpm_config, self.paths, false)?
+                {
+                    let repository_plugins: RepositoryPlugin =
+                        read_json_file(&repository_path.join("plugins.json"))?;
+                    repository_entry = repository_plugins.entry(folder_name).cloned();
+                    repository_name = repository_name_of(&repository_path);
+                    repository_path
+                        .join("plugins")
+                        .join(format!("{}.json", folder_name))
+                } else {
+                    println!(
+                        ":: {}",
+                        format!(
+                            "The plugin {} doesn't exist or is not available.",
+                            folder_name
+                        )
+                        .red()
+                        .bold()
+                    );
+                    return Ok(None);
+                }
+            } else {
+                plugin_path.join("manifest.json")
+            };
+
+        let plugin_manifest: PluginManifest = read_json_file(&plugin_manifest_path)?;
+        if refuse_downgrade(
+            &plugin_manifest.name,
+            &self.rtpm_config.plugins[plugin_index].version,
+            &plugin_manifest.version,
+            self.allow_downgrade,
+        ) || refuse_incompatible(&plugin_manifest, self.rtop_version.as_ref())?
+        {
+            return Ok(None);
+        }
+        if let Some(entry) = &repository_entry {
+            verify_repository_entry(
+                entry,
+                &plugin_manifest,
+                &sha256_hex(&std::fs::read(&plugin_manifest_path)?),
+                signed_repository(&self.rtpm_config, &repository_name),
+            )?;
+        }
+        let expected_commit: Option<String> = repository_entry.and_then(|entry| entry.commit);
+
+        let pin: Option<PluginPin> = self.rtpm_config.plugins[plugin_index].pin.clone();
+        let branch: String = match &pin {
+            Some(PluginPin::Branch(branch)) => branch.clone(),
+            Some(other_pin) => {
+                println!(
+                    ":: {}",
+                    format!(
+                        "The plugin {} is pinned to the {}, skipping.",
+                        plugin_manifest.name, other_pin
+                    )
+                    .yellow()
+                );
+                return Ok(None);
+            }
+            None => {
+                if let Some(branch) = self.rtpm_config.plugins[plugin_index].branch.clone() {
+                    branch
+                } else {
+                    let branch: String = default_branch(plugin_path).map_err(|error| {
+                        RtpmError::NotFound(format!(
+                            "Unable to find the default branch of the plugin {}: {}",
+                            plugin_manifest.name, error
+                        ))
+                    })?;
+                    self.rtpm_config.plugins[plugin_index].branch = Some(branch.clone());
+                    branch
+                }
+            }
+        };
+
+        Ok(Some(PluginUpdate {
+            id: folder_name.to_owned(),
+            path: plugin_path.to_path_buf(),
+            manifest: plugin_manifest,
+            manifest_path: plugin_manifest_path,
+            branch,
+            pin,
+            expected_commit,
+        }))
+    }
+
+    fn apply_fetch(
+        &mut self,
+        update: PluginUpdate,
+        fetch_commit_id: Oid,
+        need_compilation: bool,
+    ) -> Result<Option<PluginBuild>, RtpmError> {
+        let plugin_index: usize = if let Some(plugin_index) = self
+            .rtpm_config
+            .plugins
+            .iter()
+            .position(|r| r.id == update.id)
+        {
+            plugin_index
+        } else {
+            return Ok(None);
+        };
+        let git_repository: Repository = Repository::open(&update.path)?;
+        let fetch_commit: AnnotatedCommit =
+            git_repository.find_annotated_commit(fetch_commit_id)?;
+        let previous_commit: String = head_commit(&update.path)?;
+        if let Some(expected) = &update.expected_commit {
+            if same_commit(&previous_commit, expected) {
+                return Ok(None);
+            }
+            checkout_expected_commit(&update.path, &update.id, expected)?;
+        } else if !need_compilation {
+            return Ok(None);
+        } else if let Err(error) = do_merge(&git_repository, &update.branch, &fetch_commit) {
+            if !matches!(&error, RtpmError::Git(git_error) if git_error.message() == "no merge base found")
+            {
+                return Err(error);
+            }
             println!(
                 ":: {}",
                 format!(
-                    "An error occurred while fetching the plugin: {}",
-                    fetch.0.err().unwrap().message()
+                    "Unable to update the plugin {}, re-installation...",
+                    update.manifest.name
                 )
                 .red()
                 .bold()
             );
-            continue;
+            drop(fetch_commit);
+            drop(git_repository);
+            std::fs::remove_dir_all(&update.path)?;
+            clone(&update.manifest.url, &update.path)?;
+            if let Some(branch_pin) = &update.pin {
+                checkout_pin(&update.path, branch_pin)?;
+            }
+            println!(":: {}", "Plugin re-installed!".green());
+            return Ok(None);
+        }
+
+        let merged_manifest: PluginManifest = read_json_file(&update.manifest_path)?;
+        let refused: bool = self.rtpm_config.plugins[plugin_index].plugin_type == 1
+            && (refuse_downgrade(
+                &merged_manifest.name,
+                &self.rtpm_config.plugins[plugin_index].version,
+                &merged_manifest.version,
+                self.allow_downgrade,
+            ) || refuse_incompatible(&merged_manifest, self.rtop_version.as_ref())?);
+        let dependencies_result: Result<(), RtpmError> = if refused {
+            Ok(())
         } else {
-            need_compilation = fetch.1;
-            fetch.0.unwrap()
+            self.install_new_dependencies(&merged_manifest)
         };
-        if !need_compilation {
-            println!();
-            continue;
+        if refused || dependencies_result.is_err() {
+            let previous_object: Object =
+                git_repository.find_object(Oid::from_str(&previous_commit)?, None)?;
+            git_repository.reset(&previous_object, ResetType::Hard, None)?;
+            dependencies_result?;
+            return Ok(None);
         }
 
-        if let Err(error) = do_merge(&git_repository, "main", &fetch_commit) {
-            if error.message() == "no merge base found" {
-                println!(
-                    ":: {}",
-                    "Unable to update the plugin, re-installation..."
-                        .red()
-                        .bold()
-                );
-                std::fs::remove_dir_all(plugin.path()).unwrap();
-                clone(&plugin_manifest.url, &plugin.path());
-                println!(":: {}", "Plugin re-installed!".green());
-            } else {
-                println!(
-                    ":: {}",
+        println!(
+            ":: {}",
+            format!(
+                "Plugin {} updated, backup of the previous executable...",
+                update.manifest.name
+            )
+            .green()
+        );
+        let shared_lib_path: String = self
+            .rtop_config
+            .plugins
+            .iter()
+            .find(|r| Path::new(&r.path).starts_with(&update.path))
+            .map(|r| r.path.clone())
+            .ok_or_else(|| {
+                RtpmError::NotFound(format!(
+                    "The plugin {} is not linked to Rtop.",
+                    update.manifest.id
+                ))
+            })?;
+        let shared_lib_temp_path: PathBuf = self.paths.plugins().join(
+            Path::new(&shared_lib_path)
+                .file_name()
+                .unwrap_or_else(|| OsStr::new(&update.manifest.id)),
+        );
+        std::fs::copy(&shared_lib_path, &shared_lib_temp_path)?;
+
+        Ok(Some(PluginBuild {
+            id: update.id,
+            path: update.path,
+            manifest: update.manifest,
+            manifest_path: update.manifest_path,
+            shared_lib_path,
+            shared_lib_temp_path,
+            build_options: self.rtpm_config.plugins[plugin_index].build_options.clone(),
+        }))
+    }
+
+    fn finish_build(
+        &mut self,
+        build: PluginBuild,
+        result: Result<PathBuf, RtpmError>,
+    ) -> Result<(), RtpmError> {
+        let built_lib_path: PathBuf = match result {
+            Ok(built_lib_path) => built_lib_path,
+            Err(error) => {
+                self.failures += 1;
+                println!(":: {}", error.to_string().red());
+                print!(
+                    ":: {} ",
                     format!(
-                        "An error occurred while merging the plugin: {}",
-                        error.message()
+                        "Do you want to keep the old version of {} anyway? (y/n)",
+                        build.manifest.name
                     )
-                    .red()
-                    .bold()
+                    .purple()
                 );
+                if user_input_choice()? {
+                    println!(":: {}", "Recovery of the plugin backup...".green());
+                    std::fs::rename(build.shared_lib_temp_path, build.shared_lib_path)?;
+                    println!(":: {}", "Backup recovered!".green());
+                } else {
+                    println!(
+                        ":: {}",
+                        "Cleaning the previously installed plugin...".green()
+                    );
+                    self.rtpm_config.plugins.retain(|r| r.id != build.id);
+                    self.rtop_config
+                        .plugins
+                        .retain(|r| r.path != build.shared_lib_path);
+                    self.save()?;
+                    if self.keep_failed {
+                        keep_failed_build(&build.path, &self.paths.failed_build(&build.id))?;
+                    } else {
+                        std::fs::remove_dir_all(&build.path)?;
+                    }
+                    std::fs::remove_file(build.shared_lib_temp_path)?;
+                }
+                return Ok(());
             }
-            continue;
+        };
+        let new_plugin_manifest: PluginManifest = read_json_file(&build.manifest_path)?;
+        let commit: String = head_commit(&build.path)?;
+        let manifest_hash: String = sha256_hex(&std::fs::read(&build.manifest_path)?);
+        // The library moves to `lib/` when the shared target directory is enabled.
+        let built_lib_string: String = built_lib_path.to_string_lossy().into_owned();
+        for rtop_plugin in self
+            .rtop_config
+            .plugins
+            .iter_mut()
+            .filter(|r| r.path == build.shared_lib_path)
+        {
+            rtop_plugin.path = built_lib_string.clone();
         }
+        if let Some(element) = self
+            .rtpm_config
+            .plugins
+            .iter_mut()
+            .find(|r| r.id == build.id)
+        {
+            if build.manifest.version != new_plugin_manifest.version {
+                element.version = new_plugin_manifest.version;
+            }
+            element.rtop = new_plugin_manifest.rtop;
+            element.dependencies = new_plugin_manifest.dependencies;
+            element.conflicts = new_plugin_manifest.conflicts;
+            element.commit = Some(commit);
+            element.manifest_hash = Some(manifest_hash);
+            element.build_timestamp = Some(current_timestamp());
+        }
+        self.save()?;
+        drop(std::fs::remove_file(&build.shared_lib_temp_path));
+        println!(
+            ":: {}",
+            format!(
+                "Update of the plugin: {} ({}) is terminated!",
+                build.manifest.name.bold(),
+                build.manifest.url
+            )
+            .green()
+        );
+        Ok(())
+    }
 
-        println!(":: {}", "Plugin updated, compilation...".green());
-
-        let plugin_cargo_toml_path: PathBuf = dirs::data_dir()
-            .unwrap()
-            .join("rtop")
-            .join("plugins")
-            .join(plugin_manifest.id.clone())
-            .join("Cargo.toml");
-
-        println!(":: {}", "Backup previous executable...".green());
-
-        let rtop_plugin_index: usize = rtop_config
+    // Prebuilt plugins have no git checkout, the artifact is replaced when its checksum changes.
+    fn update_artifact(&mut self, id: &str) -> Result<(), RtpmError> {
+        let element: RTPMConfigPluginElement = self
+            .rtpm_config
             .plugins
             .iter()
-            .position(|r| {
-                r.path.starts_with(
-                    &plugin
-                        .path()
-                        .join("target")
-                        .into_os_string()
-                        .into_string()
-                        .unwrap(),
+            .find(|r| r.id == id)
+            .cloned()
+            .ok_or_else(|| RtpmError::NotFound(format!("The plugin {} is not installed.", id)))?;
+        if let Some(pin) = &element.pin {
+            println!(
+                ":: {}",
+                format!(
+                    "The plugin {} is pinned to the {}, skipping.",
+                    element.name, pin
                 )
-            })
-            .unwrap();
-        let shared_lib_path: String = rtop_config.plugins[rtop_plugin_index].path.clone();
-        let shared_lib_temp_path: PathBuf = plugins_path
-            .clone()
-            .join(Path::new(&shared_lib_path).file_name().unwrap());
-        std::fs::copy(shared_lib_path.clone(), shared_lib_temp_path.clone()).unwrap();
-
-        let cargo_build: bool = build_cargo_project(&plugin_cargo_toml_path);
-        if !cargo_build {
-            println!(":: {}", "An error occurred during compilation!".red());
-            print!(
-                ":: {} ",
-                "Do you want to keep the old version anyway? (y/n)".purple()
+                .yellow()
             );
-            if user_input_choice() {
-                println!(":: {}", "Recovery of the plugin backup...".green());
-                std::fs::rename(shared_lib_temp_path, shared_lib_path).unwrap();
-                println!(":: {}", "Backup recovered!".green());
+            return Ok(());
+        }
+        let (plugin_manifest, manifest_hash, artifact_base, _checkout): (
+            PluginManifest,
+            String,
+            ArtifactBase,
+            Option<ManifestCheckout>,
+        ) = if element.plugin_type == 0 {
+            let recorded_repository: Option<PathBuf> = element
+                .repository
+                .as_ref()
+                .map(|repository| self.paths.repository(repository))
+                .filter(|path| {
+                    path.join("plugins")
+                        .join(format!("{}.json", element.id))
+                        .exists()
+                });
+            let repository_path: PathBuf = if let Some(repository_path) = recorded_repository {
+                repository_path
+            } else if let Some(repository_path) =
+                search_plugin(&element.id, &self.rtpm_config, self.paths, false)?
+            {
+                repository_path
             } else {
-                println!(
-                    ":: {}",
-                    "Cleaning the previously installed plugin...".green()
-                );
-                let rtpm_plugin_index: usize = rtpm_config
-                    .plugins
-                    .iter()
-                    .position(|r| r.id == plugin_manifest.id)
-                    .unwrap();
-                rtpm_config.plugins.remove(rtpm_plugin_index);
-
-                let rtop_plugin_index: usize = rtop_config
-                    .plugins
-                    .iter()
-                    .position(|r| {
-                        r.path.starts_with(
-                            &plugin
-                                .path()
-                                .join("target")
-                                .into_os_string()
-                                .into_string()
-                                .unwrap(),
-                        )
-                    })
-                    .unwrap();
-                rtop_config.plugins.remove(rtop_plugin_index);
-                std::fs::remove_dir_all(plugin.path()).unwrap();
-                save_json_to_file(&rtpm_config, rtpm_config_path.clone());
-                save_json_to_file(&rtop_config, rtop_config_path.clone());
-                std::fs::remove_file(shared_lib_temp_path).unwrap();
+                return Err(RtpmError::NotFound(format!(
+                    "The plugin {} doesn't exist or is not available.",
+                    element.id
+                )));
+            };
+            let manifest_path: PathBuf = repository_path
+                .join("plugins")
+                .join(format!("{}.json", element.id));
+            let manifest: PluginManifest = read_json_file(&manifest_path)?;
+            let hash: String = sha256_hex(&std::fs::read(&manifest_path)?);
+            let repository_plugins: RepositoryPlugin =
+                read_json_file(&repository_path.join("plugins.json"))?;
+            if let Some(entry) = repository_plugins.entry(&element.id) {
+                verify_repository_entry(
+                    entry,
+                    &manifest,
+                    &hash,
+                    signed_repository(&self.rtpm_config, &repository_name_of(&repository_path)),
+                )?;
             }
-            continue;
+            (
+                manifest,
+                hash,
+                ArtifactBase::Directory(repository_path),
+                None,
+            )
+        } else if let Some(remote_manifest) = fetch_remote_manifest(
+            &element.repo,
+            element.pin.as_ref().map(PluginPin::reference),
+            self.paths,
+        )? {
+            (
+                remote_manifest.manifest,
+                remote_manifest.manifest_hash,
+                remote_manifest.artifact_base,
+                remote_manifest.checkout,
+            )
+        } else {
+            return Ok(());
+        };
+        println!(
+            ":: {}",
+            format!(
+                "Updating the plugin: {} ({})...",
+                plugin_manifest.name.bold(),
+                plugin_manifest.url
+            )
+            .green()
+        );
+        if refuse_downgrade(
+            &plugin_manifest.name,
+            &element.version,
+            &plugin_manifest.version,
+            self.allow_downgrade,
+        ) || refuse_incompatible(&plugin_manifest, self.rtop_version.as_ref())?
+        {
+            return Ok(());
         }
 
+        let artifact: &PluginArtifact = if let Some((_, artifact)) =
+            select_artifact(&plugin_manifest)
+        {
+            artifact
+        } else {
+            println!(
+            ":: {}\n",
+            format!(
+                "No prebuilt artifact matches this platform anymore, reinstall the plugin with {} to compile it.",
+                "rtpm -S --build-from-source".bold()
+            )
+            .yellow()
+        );
+            return Ok(());
+        };
+        if element.artifact.as_deref() == Some(artifact.sha256.to_lowercase().as_str()) {
+            println!();
+            return Ok(());
+        }
+        self.install_new_dependencies(&plugin_manifest)?;
+
+        println!(":: {}", "Downloading the new prebuilt artifact...".green());
+        let staging_path: PathBuf = self.paths.staging().join(&element.id);
+        if staging_path.exists() {
+            std::fs::remove_dir_all(&staging_path)?;
+        }
+        let staged_artifact: PathBuf =
+            match fetch_artifact(artifact, Some(&artifact_base), &staging_path) {
+                Ok(staged_artifact) => staged_artifact,
+                Err(error) => {
+                    if staging_path.exists() {
+                        std::fs::remove_dir_all(&staging_path)?;
+                    }
+                    return Err(error);
+                }
+            };
+        let release_path: PathBuf = self
+            .paths
+            .plugin(&element.id)
+            .join("target")
+            .join("release");
+        std::fs::create_dir_all(&release_path)?;
+        let shared_lib_path: PathBuf = release_path.join(
+            staged_artifact
+                .file_name()
+                .unwrap_or_else(|| OsStr::new(&element.id)),
+        );
+        std::fs::rename(&staged_artifact, &shared_lib_path)?;
+        std::fs::remove_dir_all(&staging_path)?;
+
+        let shared_lib_string: String = shared_lib_path.to_string_lossy().into_owned();
+        let mut old_libraries: Vec<String> = Vec::new();
+        for rtop_plugin in self
+            .rtop_config
+            .plugins
+            .iter_mut()
+            .filter(|r| r.name == element.id)
+        {
+            if rtop_plugin.path != shared_lib_string {
+                old_libraries.push(std::mem::replace(
+                    &mut rtop_plugin.path,
+                    shared_lib_string.clone(),
+                ));
+            }
+        }
+        if let Some(updated) = self
+            .rtpm_config
+            .plugins
+            .iter_mut()
+            .find(|r| r.id == element.id)
+        {
+            updated.version = plugin_manifest.version.clone();
+            updated.manifest_hash = Some(manifest_hash);
+            updated.artifact = Some(artifact.sha256.to_lowercase());
+            updated.build_timestamp = Some(current_timestamp());
+            updated.dependencies = plugin_manifest.dependencies.clone();
+            updated.conflicts = plugin_manifest.conflicts.clone();
+            updated.rtop = plugin_manifest.rtop.clone();
+        }
+        // Rtop no longer points to the old libraries once the configs are saved.
+        self.save()?;
+        for library in old_libraries {
+            drop(std::fs::remove_file(library));
+        }
         println!(
             ":: {}",
             format!(
@@ -200,14 +719,45 @@ pub fn update_packages() {
             )
             .green()
         );
-        let new_plugin_manifest: PluginManifest = read_json_file(&plugin_manifest_path);
-        if plugin_manifest.version != new_plugin_manifest.version {
-            rtpm_config.plugins[plugin_index].version = new_plugin_manifest.version;
-        }
+        Ok(())
+    }
+}
+
+fn repository_name_of(repository_path: &Path) -> String {
+    repository_path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+fn refuse_downgrade(name: &str, installed: &str, available: &str, allow_downgrade: bool) -> bool {
+    if allow_downgrade || compare_versions(installed, available) != VersionChange::Downgrade {
+        return false;
     }
-    save_json_to_file(&rtpm_config, rtpm_config_path);
     println!(
-        ":: {}",
-        "Update of all Rtop plugins completed!".green().bold()
+        ":: {}\n",
+        format!(
+            "The available version {} of {} is older than the installed version {}, use --allow-downgrade to install it.",
+            available, name, installed
+        )
+        .yellow()
     );
+    true
+}
+
+fn refuse_incompatible(
+    plugin_manifest: &PluginManifest,
+    rtop_version: Option<&Version>,
+) -> Result<bool, RtpmError> {
+    match verify_rtop_compatibility(
+        &plugin_manifest.id,
+        plugin_manifest.rtop.as_deref(),
+        rtop_version,
+    ) {
+        Ok(()) => Ok(false),
+        Err(error @ RtpmError::IncompatibleRtop { .. }) => {
+            println!(":: {}\n", format!("{} Skipping.", error).yellow());
+            Ok(true)
+        }
+        Err(error) => Err(error),
+    }
 }
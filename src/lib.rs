@@ -18,8 +18,6 @@
     clippy::exhaustive_structs,
     clippy::single_char_lifetime_names,
     clippy::integer_division,
-    clippy::separated_literal_suffix,
-    clippy::else_if_without_else,
     clippy::indexing_slicing,
     clippy::cast_possible_truncation
 )]
@@ -32,7 +30,7 @@
     clippy::wildcard_imports,
     clippy::single_match_else,
     clippy::single_match,
-    clippy::let_underscore_drop,
+    let_underscore_drop,
     clippy::shadow_reuse,
     clippy::expect_used,
     clippy::suboptimal_flops,
@@ -1,29 +1,37 @@
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use colored::Colorize;
+use rtpm::git::credentials::load_credentials;
+use rtpm::util::build::load_build_config;
+use rtpm::util::error::RtpmError;
+use rtpm::util::lock::RtpmLock;
+use rtpm::util::network::load_network_config;
+use rtpm::util::paths::Paths;
 use rtpm::util::structs::RTPMConfig;
 use rtpm::util::utils::save_json_to_file;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-fn main() {
-    let rtop_data_dir: PathBuf = dirs::data_dir().unwrap_or_else(|| {
-        println!("Your system is not supported, please open an issue at: https://github.com/RtopRS/RtopPluginManager/issues/new so we can add support for your system.");
-        std::process::exit(9);
-    }).join("rtop");
-
-    std::fs::create_dir_all(rtop_data_dir.join("plugins")).unwrap();
-    std::fs::create_dir_all(rtop_data_dir.join("repositories")).unwrap();
-
-    let rtop_config_dir: PathBuf = dirs::config_dir().unwrap().join("rtop");
-    std::fs::create_dir_all(&rtop_config_dir).unwrap();
+fn exit_code(error: &RtpmError) -> i32 {
+    match error {
+        RtpmError::UserAbort => 0,
+        RtpmError::NotFound(_) => 9,
+        RtpmError::InvalidInput(_) => 22,
+        RtpmError::Locked(_) => 11,
+        _ => 1,
+    }
+}
 
-    let config_path: PathBuf = rtop_config_dir.join("rtpm.json");
-    if !config_path.exists() {
-        let config: RTPMConfig = RTPMConfig {
-            repositories: Vec::new(),
-            plugins: Vec::new(),
-        };
-        save_json_to_file(&config, config_path);
+fn main() {
+    if let Err(error) = run() {
+        if let RtpmError::UserAbort = error {
+            println!(":: {}", "Exiting...".blue());
+        } else {
+            println!(":: {}", error.to_string().red().bold());
+        }
+        std::process::exit(exit_code(&error));
     }
+}
 
+fn run() -> Result<(), RtpmError> {
     let app: Command = Command::new("RtopPluginManager")
         .about("The official tool to simplify the management of plugins for Rtop.")
         .version("0.0.1")
@@ -33,6 +41,40 @@ fn main() {
         .subcommand_required(false)
         .arg_required_else_help(true)
         .author("Rtop Development Team")
+        .arg(
+            Arg::new("root")
+                .help("The directory where RTPM stores its plugins, repositories and config.")
+                .long("root")
+                .global(true)
+                .takes_value(true)
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("offline")
+                .help("Forbid any network access, plugins are installed from the local source cache.")
+                .long("offline")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::new("jobs")
+                .help("The number of repositories and plugins updated in parallel, the number of CPUs by default.")
+                .long("jobs")
+                .short('j')
+                .global(true)
+                .takes_value(true)
+                .value_name("N")
+                .value_parser(clap::value_parser!(u16).range(1..)),
+        )
+        .arg(
+            Arg::new("wait")
+                .help("Wait for another running rtpm to finish instead of exiting.")
+                .long("wait")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .takes_value(false),
+        )
         .subcommand(
             Command::new("install")
                 .short_flag('S')
@@ -53,6 +95,14 @@ fn main() {
                         .action(ArgAction::SetTrue)
                         .takes_value(false)
                 )
+                .arg(
+                    Arg::new("allow-downgrade")
+                        .help("This flag allows the upgrade to install an older version than the installed one.")
+                        .requires("upgrade")
+                        .long("allow-downgrade")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false)
+                )
                 .arg(
                     Arg::new("unsecure-git-url")
                         .help("This flag allows to download a plugin from a git repo.")
@@ -61,9 +111,74 @@ fn main() {
                         .action(ArgAction::SetTrue)
                         .takes_value(false)
                 )
+                .arg(
+                    Arg::new("build-from-source")
+                        .help("This flag allows to compile plugins even when a prebuilt artifact is available.")
+                        .conflicts_with_all(["upgrade", "locked"].as_ref())
+                        .long("build-from-source")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false)
+                )
+                .arg(
+                    Arg::new("keep-failed")
+                        .help("This flag allows to keep the directory of a failed build in the failed folder of RTPM.")
+                        .long("keep-failed")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false)
+                )
+                .arg(
+                    Arg::new("features")
+                        .help("The cargo features of the plugins, the ones recommended by the manifest by default.")
+                        .conflicts_with_all(["upgrade", "locked"].as_ref())
+                        .long("features")
+                        .takes_value(true)
+                        .value_name("FEATURES")
+                        .multiple_occurrences(true)
+                )
+                .arg(
+                    Arg::new("no-default-features")
+                        .help("This flag allows to build plugins without their default cargo features.")
+                        .conflicts_with_all(["upgrade", "locked"].as_ref())
+                        .long("no-default-features")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false)
+                )
+                .arg(
+                    Arg::new("profile")
+                        .help("The cargo profile used to build plugins, release by default.")
+                        .conflicts_with_all(["upgrade", "locked"].as_ref())
+                        .long("profile")
+                        .takes_value(true)
+                        .value_name("PROFILE")
+                )
+                .arg(
+                    Arg::new("target")
+                        .help("The target triple plugins are built for, the host by default.")
+                        .conflicts_with_all(["upgrade", "locked"].as_ref())
+                        .long("target")
+                        .takes_value(true)
+                        .value_name("TRIPLE")
+                )
+                .arg(
+                    Arg::new("env")
+                        .help("An environment variable set for the build, like RUSTFLAGS=-Ctarget-cpu=native.")
+                        .conflicts_with_all(["upgrade", "locked"].as_ref())
+                        .long("env")
+                        .takes_value(true)
+                        .value_name("KEY=VALUE")
+                        .multiple_occurrences(true)
+                )
+                .arg(
+                    Arg::new("locked")
+                        .help("This flag allows to install plugins at the commits recorded in the RTPM config.")
+                        .conflicts_with_all(["upgrade", "unsecure-git-url"].as_ref())
+                        .long("locked")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false)
+                )
                 .arg(
                     Arg::new("plugins")
-                        .help("The plugin name or git repository URL.")
+                        .help("The plugin name or git repository URL, suffixed by @tag, @commit or #branch to pin it.")
                         .conflicts_with("upgrade")
                         .takes_value(true)
                         .multiple_values(true),
@@ -81,11 +196,31 @@ fn main() {
                         .multiple_values(true),
                 ),
         )
+        .subcommand(
+            Command::new("outdated")
+                .short_flag('O')
+                .long_flag("outdated")
+                .about("List installed plugins with a newer version in the repositories.")
+                .arg(
+                    Arg::new("allow-downgrade")
+                        .help("This flag allows to list plugins whose available version is older.")
+                        .long("allow-downgrade")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false),
+                ),
+        )
         .subcommand(
             Command::new("uninstall")
                 .short_flag('U')
                 .long_flag("uninstall")
                 .about("Uninstall a Rtop plugin.")
+                .arg(
+                    Arg::new("cascade")
+                        .help("This flag allows to also uninstall the plugins that depend on the given plugins.")
+                        .long("cascade")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false),
+                )
                 .arg(
                     Arg::new("plugins")
                         .help("The plugin(s) name.")
@@ -100,9 +235,9 @@ fn main() {
                 .about("Add custom Rtop plugin repository.")
                 .arg(
                     Arg::new("repository")
-                        .help("The repository URL.")
+                        .help("The repository URL, followed by optional mirrors tried in order.")
                         .takes_value(true)
-                        .multiple_values(false),
+                        .multiple_values(true),
                 ),
         )
         .subcommand(
@@ -117,6 +252,48 @@ fn main() {
                         .multiple_values(false),
                 ),
         )
+        .subcommand(
+            Command::new("logs")
+                .short_flag('L')
+                .long_flag("logs")
+                .about("Show the latest build log of a plugin.")
+                .arg(
+                    Arg::new("plugin")
+                        .help("The plugin name.")
+                        .takes_value(true)
+                        .multiple_values(false),
+                ),
+        )
+        .subcommand(
+            Command::new("cache")
+                .short_flag('C')
+                .long_flag("cache")
+                .about("Show the size of the build and source caches, prune them or configure the shared build cache.")
+                .arg(
+                    Arg::new("prune")
+                        .help("This flag allows to delete the shared build cache.")
+                        .long("prune")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::new("sources")
+                        .help("This flag allows to also delete the plugin sources used by --offline.")
+                        .requires("prune")
+                        .long("sources")
+                        .action(ArgAction::SetTrue)
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::new("shared-target")
+                        .help("Build every plugin in the shared build cache so their dependencies are compiled once (on) or in their own directory (off).")
+                        .conflicts_with("prune")
+                        .long("shared-target")
+                        .value_name("on|off")
+                        .value_parser(["on", "off"])
+                        .takes_value(true),
+                ),
+        )
         .subcommand(
         Command::new("infos")
             .short_flag('I')
@@ -153,17 +330,64 @@ fn main() {
             ),
     );
 
-    match app.get_matches().subcommand() {
-        Some(("install", matches)) => rtpm::commands::install::install(matches),
-        Some(("infos", matches)) => rtpm::commands::infos::infos(matches),
-        Some(("search", matches)) => rtpm::commands::search::search(matches),
-        Some(("uninstall", matches)) => rtpm::commands::uninstall::uninstall(matches),
+    let app_matches: ArgMatches = app.get_matches();
+    let paths: Paths = Paths::resolve(app_matches.get_one::<String>("root").map(Path::new))?;
+
+    std::fs::create_dir_all(paths.plugins())?;
+    std::fs::create_dir_all(paths.repositories())?;
+    std::fs::create_dir_all(&paths.config_dir)?;
+
+    // Taken before the configs are created or read, `cache` only writes with `--prune` or `--shared-target`.
+    let wait: bool = app_matches.get_one::<bool>("wait") == Some(&true);
+    let lock: Option<RtpmLock> = match app_matches.subcommand() {
+        Some(("install" | "uninstall" | "add-repository" | "remove-repository", _)) => {
+            Some(RtpmLock::acquire(&paths, wait)?)
+        }
+        Some(("cache", matches))
+            if matches.get_one::<bool>("prune") == Some(&true)
+                || matches.contains_id("shared-target") =>
+        {
+            Some(RtpmLock::acquire(&paths, wait)?)
+        }
+        _ => None,
+    };
+
+    let config_path: PathBuf = paths.rtpm_config();
+    if !config_path.exists() {
+        // The other commands only lock to write the default config, another rtpm may have written it meanwhile.
+        let creation_lock: Option<RtpmLock> = if lock.is_none() {
+            Some(RtpmLock::acquire(&paths, true)?)
+        } else {
+            None
+        };
+        if !config_path.exists() {
+            let config: RTPMConfig = RTPMConfig::default();
+            save_json_to_file(&config, config_path)?;
+        }
+        drop(creation_lock);
+    }
+
+    load_credentials(&paths)?;
+    load_network_config(
+        &paths,
+        app_matches.get_one::<bool>("offline") == Some(&true),
+    )?;
+    load_build_config(&paths)?;
+
+    match app_matches.subcommand() {
+        Some(("install", matches)) => rtpm::commands::install::install(matches, &paths),
+        Some(("infos", matches)) => rtpm::commands::infos::infos(matches, &paths),
+        Some(("logs", matches)) => rtpm::commands::logs::logs(matches, &paths),
+        Some(("cache", matches)) => rtpm::commands::cache::cache(matches, &paths),
+        Some(("search", matches)) => rtpm::commands::search::search(matches, &paths),
+        Some(("outdated", matches)) => rtpm::commands::outdated::outdated(matches, &paths),
+        Some(("uninstall", matches)) => rtpm::commands::uninstall::uninstall(matches, &paths),
         Some(("add-repository", matches)) => {
-            rtpm::commands::add_repository::add_repository(matches)
+            rtpm::commands::add_repository::add_repository(matches, &paths)
         }
         Some(("remove-repository", matches)) => {
-            rtpm::commands::remove_repository::remove_repository(matches)
+            rtpm::commands::remove_repository::remove_repository(matches, &paths)
         }
-        _ => {}
+        _ => Ok(()),
     }
 }
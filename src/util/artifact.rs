@@ -0,0 +1,104 @@
+use crate::util::error::RtpmError;
+use crate::util::network::http_client;
+use crate::util::platform::{matches_platform, Host};
+use crate::util::structs::{PluginArtifact, PluginManifest};
+use crate::util::utils::sha256_hex;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub enum ArtifactBase {
+    Directory(PathBuf),
+    Url(Url),
+}
+
+pub fn select_artifact(plugin_manifest: &PluginManifest) -> Option<(&str, &PluginArtifact)> {
+    let host: Host = Host::current();
+    plugin_manifest
+        .artifacts
+        .iter()
+        .find(|(target, _)| matches_platform(target, &host).is_ok())
+        .map(|(target, artifact)| (target.as_str(), artifact))
+}
+
+// Downloads or copies the artifact in `destination_directory` once its checksum is verified.
+pub fn fetch_artifact(
+    artifact: &PluginArtifact,
+    base: Option<&ArtifactBase>,
+    destination_directory: &Path,
+) -> Result<PathBuf, RtpmError> {
+    let (file_name, content): (String, Vec<u8>) = if let Some(url) = &artifact.url {
+        let artifact_url: Url = Url::parse(url).map_err(|error| {
+            RtpmError::InvalidInput(format!("The artifact URL {} is invalid ({}).", url, error))
+        })?;
+        (
+            file_name_of(artifact_url.path())?,
+            http_client()?
+                .get(artifact_url)
+                .send()?
+                .error_for_status()?
+                .bytes()?
+                .to_vec(),
+        )
+    } else if let Some(path) = &artifact.path {
+        if Path::new(path)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(RtpmError::InvalidInput(format!(
+                "The artifact path {} must stay inside its repository.",
+                path
+            )));
+        }
+        let content: Vec<u8> = match base {
+            Some(ArtifactBase::Directory(directory)) => std::fs::read(directory.join(path))?,
+            Some(ArtifactBase::Url(base_url)) => {
+                let artifact_url: Url = base_url.join(path).map_err(|error| {
+                    RtpmError::InvalidInput(format!(
+                        "The artifact path {} is invalid ({}).",
+                        path, error
+                    ))
+                })?;
+                http_client()?
+                    .get(artifact_url)
+                    .send()?
+                    .error_for_status()?
+                    .bytes()?
+                    .to_vec()
+            }
+            None => {
+                return Err(RtpmError::InvalidInput(format!(
+                    "The artifact path {} has no repository to be resolved from.",
+                    path
+                )))
+            }
+        };
+        (file_name_of(path)?, content)
+    } else {
+        return Err(RtpmError::InvalidInput(
+            "An artifact must have an url or a path.".to_owned(),
+        ));
+    };
+
+    let checksum: String = sha256_hex(&content);
+    if !checksum.eq_ignore_ascii_case(&artifact.sha256) {
+        return Err(RtpmError::ChecksumMismatch {
+            subject: format!("the artifact {}", file_name),
+            expected: artifact.sha256.clone(),
+            actual: checksum,
+        });
+    }
+    std::fs::create_dir_all(destination_directory)?;
+    let destination: PathBuf = destination_directory.join(file_name);
+    std::fs::write(&destination, content)?;
+    Ok(destination)
+}
+
+fn file_name_of(path: &str) -> Result<String, RtpmError> {
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            RtpmError::InvalidInput(format!("Unable to find the file name of {}.", path))
+        })
+}
@@ -0,0 +1,321 @@
+use crate::util::error::RtpmError;
+use crate::util::network::is_offline;
+use crate::util::paths::Paths;
+use crate::util::structs::{BuildBackendKind, BuildConfig, BuildOptions, RTPMConfig};
+use crate::util::utils::{read_json_file, set_once};
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::thread::JoinHandle;
+
+// Loaded once by `main`, like the network configuration.
+static BUILD: OnceLock<BuildConfig> = OnceLock::new();
+
+pub fn load_build_config(paths: &Paths) -> Result<(), RtpmError> {
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    if rtpm_config.build.backend == Some(BuildBackendKind::Embedded)
+        && !cfg!(feature = "embedded-cargo")
+    {
+        return Err(RtpmError::InvalidInput(
+            "The embedded build backend is not available, rtpm was built without the embedded-cargo feature.".to_owned(),
+        ));
+    }
+    set_once(&BUILD, rtpm_config.build, "build")
+}
+
+pub fn build_backend() -> Box<dyn BuildBackend> {
+    let build_config: &BuildConfig = BUILD.get_or_init(BuildConfig::default);
+    #[cfg(feature = "embedded-cargo")]
+    if build_config.backend == Some(BuildBackendKind::Embedded) {
+        return Box::new(EmbeddedCargo);
+    }
+    Box::new(ExternalCargo {
+        program: build_config
+            .cargo
+            .clone()
+            .map_or_else(|| OsString::from("cargo"), PathBuf::into_os_string),
+    })
+}
+
+pub struct BuildJob<'a> {
+    pub toml_path: &'a Path,
+    pub target_dir: Option<&'a Path>,
+    pub build_options: &'a BuildOptions,
+    pub jobs: Option<u32>,
+}
+
+pub trait BuildBackend {
+    // Returns the produced shared library when the backend knows it, the cargo output goes to `output`.
+    fn compile(&self, job: &BuildJob<'_>, output: &BuildOutput) -> Result<Option<PathBuf>, String>;
+}
+
+// Runs the cargo of the user, rustup then honours the `rust-toolchain.toml` of the plugin.
+pub struct ExternalCargo {
+    pub program: OsString,
+}
+
+impl BuildBackend for ExternalCargo {
+    fn compile(&self, job: &BuildJob<'_>, output: &BuildOutput) -> Result<Option<PathBuf>, String> {
+        let mut command: Command = Command::new(&self.program);
+        command
+            .current_dir(job.toml_path.parent().unwrap_or_else(|| Path::new(".")))
+            .args([
+                "build",
+                "--message-format=json-render-diagnostics",
+                "--profile",
+                job.build_options.profile(),
+                "--manifest-path",
+            ])
+            .arg(job.toml_path)
+            .envs(&job.build_options.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if !job.build_options.features.is_empty() {
+            command
+                .arg("--features")
+                .arg(job.build_options.features.join(","));
+        }
+        if job.build_options.no_default_features {
+            command.arg("--no-default-features");
+        }
+        if let Some(target) = &job.build_options.target {
+            command.args(["--target", target]);
+        }
+        if let Some(target_dir) = job.target_dir {
+            command.arg("--target-dir").arg(target_dir);
+        }
+        if let Some(build_jobs) = job.jobs {
+            command.args(["--jobs", &build_jobs.max(1).to_string()]);
+        }
+        if is_offline() {
+            command.arg("--offline");
+        }
+
+        let mut child: Child = command.spawn().map_err(|error| {
+            format!(
+                "unable to run {} ({}), a Rust toolchain can be installed with rustup: https://rustup.rs",
+                self.program.to_string_lossy(),
+                error
+            )
+        })?;
+        let mut stderr_output: BuildOutput = output.clone();
+        let stderr_reader: Option<JoinHandle<()>> = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                drop(std::io::copy(&mut stderr, &mut stderr_output));
+            })
+        });
+        let mut messages: String = String::new();
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout
+                .read_to_string(&mut messages)
+                .map_err(|e| e.to_string())?;
+        }
+        if let Some(reader) = stderr_reader {
+            drop(reader.join());
+        }
+        let status: ExitStatus = child.wait().map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("cargo failed ({})", status));
+        }
+        Ok(cdylib_from_messages(&messages, job.toml_path))
+    }
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    #[serde(default)]
+    manifest_path: Option<PathBuf>,
+    #[serde(default)]
+    target: Option<CargoTarget>,
+    #[serde(default)]
+    filenames: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct CargoTarget {
+    kind: Vec<String>,
+}
+
+// The cdylib of the package at `toml_path`, the cdylibs of its dependencies are never returned.
+pub fn cdylib_from_messages(messages: &str, toml_path: &Path) -> Option<PathBuf> {
+    let manifest_path: PathBuf =
+        std::fs::canonicalize(toml_path).unwrap_or_else(|_| toml_path.to_path_buf());
+    let mut library: Option<PathBuf> = None;
+    for line in messages.lines() {
+        let Ok(message) = serde_json::from_str::<CargoMessage>(line) else {
+            continue;
+        };
+        if message.reason != "compiler-artifact"
+            || message.manifest_path.as_deref() != Some(manifest_path.as_path())
+            || !message
+                .target
+                .is_some_and(|target| target.kind.iter().any(|kind| kind == "cdylib"))
+        {
+            continue;
+        }
+        library = message
+            .filenames
+            .into_iter()
+            .find(|filename| is_shared_library(filename))
+            .or(library);
+    }
+    library
+}
+
+#[derive(Deserialize)]
+struct CargoManifest {
+    package: Option<CargoPackage>,
+    lib: Option<CargoPackage>,
+}
+
+#[derive(Deserialize)]
+struct CargoPackage {
+    name: Option<String>,
+}
+
+// The crate name of the library, from `[lib]` or else `[package]`, as cargo names the produced file.
+pub fn library_name(toml_path: &Path) -> Result<Option<String>, RtpmError> {
+    let content: String =
+        std::fs::read_to_string(toml_path).map_err(|error| RtpmError::ConfigIo {
+            path: toml_path.to_path_buf(),
+            error,
+        })?;
+    let manifest: CargoManifest = toml::from_str(&content).map_err(|error| {
+        RtpmError::InvalidInput(format!(
+            "The file {} is malformed ({}).",
+            toml_path.display(),
+            error
+        ))
+    })?;
+    Ok(manifest
+        .lib
+        .and_then(|lib| lib.name)
+        .or_else(|| manifest.package.and_then(|package| package.name))
+        .map(|name| name.replace('-', "_")))
+}
+
+pub fn is_shared_library(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| ["so", "dll", "dylib"].contains(&&*extension.to_string_lossy()))
+}
+
+#[cfg(feature = "embedded-cargo")]
+pub struct EmbeddedCargo;
+
+#[cfg(feature = "embedded-cargo")]
+impl BuildBackend for EmbeddedCargo {
+    fn compile(&self, job: &BuildJob<'_>, output: &BuildOutput) -> Result<Option<PathBuf>, String> {
+        use cargo::core::compiler::{Compilation, CompileKind, CompileMode, CompileTarget};
+        use cargo::core::resolver::CliFeatures;
+        use cargo::core::{Shell, Workspace};
+        use cargo::ops::CompileOptions;
+        use cargo::util::interning::InternedString;
+        use cargo::util::{homedir, Config};
+        use std::collections::HashMap;
+
+        let cwd: PathBuf = std::env::current_dir().map_err(|e| e.to_string())?;
+        let cargo_home: PathBuf =
+            homedir(&cwd).ok_or_else(|| "unable to find the cargo home directory".to_owned())?;
+        let mut config: Config =
+            Config::new(Shell::from_write(Box::new(output.clone())), cwd, cargo_home);
+        if !job.build_options.env.is_empty() {
+            let mut environment: HashMap<String, String> = std::env::vars().collect();
+            environment.extend(job.build_options.env.clone());
+            config.set_env(environment);
+        }
+        config
+            .configure(
+                0,
+                false,
+                None,
+                false,
+                false,
+                is_offline(),
+                &job.target_dir.map(Path::to_path_buf),
+                &[],
+                &[],
+            )
+            .map_err(|e| e.to_string())?;
+        let workspace: Workspace =
+            Workspace::new(job.toml_path, &config).map_err(|e| e.to_string())?;
+        let mut compile_options: CompileOptions =
+            CompileOptions::new(&config, CompileMode::Build).map_err(|e| e.to_string())?;
+        compile_options.build_config.requested_profile =
+            InternedString::new(job.build_options.profile());
+        compile_options.cli_features = CliFeatures::from_command_line(
+            &job.build_options.features,
+            false,
+            !job.build_options.no_default_features,
+        )
+        .map_err(|e| e.to_string())?;
+        if let Some(target) = &job.build_options.target {
+            compile_options.build_config.requested_kinds = vec![CompileKind::Target(
+                CompileTarget::new(target).map_err(|e| e.to_string())?,
+            )];
+        }
+        if let Some(build_jobs) = job.jobs {
+            compile_options.build_config.jobs = build_jobs.max(1);
+        }
+        let compilation: Compilation =
+            cargo::ops::compile(&workspace, &compile_options).map_err(|e| e.to_string())?;
+        // As with the external cargo, the cdylibs of the dependencies are never returned.
+        let manifest_path: PathBuf =
+            std::fs::canonicalize(job.toml_path).unwrap_or_else(|_| job.toml_path.to_path_buf());
+        let crate_name: Option<String> = library_name(job.toml_path).map_err(|e| e.to_string())?;
+        Ok(compilation
+            .cdylibs
+            .into_iter()
+            .filter(|output| {
+                std::fs::canonicalize(output.unit.pkg.manifest_path())
+                    .is_ok_and(|path| path == manifest_path)
+                    && crate_name
+                        .as_deref()
+                        .is_none_or(|name| output.unit.target.crate_name() == name)
+            })
+            .last()
+            .map(|output| output.path))
+    }
+}
+
+// Keeps the cargo output for the build log, it is echoed on stderr when `echo` is set.
+#[derive(Clone)]
+pub struct BuildOutput {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    echo: bool,
+}
+
+impl BuildOutput {
+    pub fn new(echo: bool) -> Self {
+        Self {
+            buffer: Arc::default(),
+            echo,
+        }
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.lock().unwrap_or_else(PoisonError::into_inner))
+            .into_owned()
+    }
+}
+
+impl Write for BuildOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.echo {
+            std::io::stderr().write_all(buf)?;
+        }
+        self.buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
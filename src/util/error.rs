@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum RtpmError {
+    Git(git2::Error),
+    Network(reqwest::Error),
+    ManifestParse {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    Build {
+        plugin: String,
+        message: String,
+    },
+    ConfigIo {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Io(std::io::Error),
+    NotFound(String),
+    InvalidInput(String),
+    Locked(PathBuf),
+    Offline(String),
+    Dependency(String),
+    Signature {
+        repository: String,
+        message: String,
+    },
+    ChecksumMismatch {
+        subject: String,
+        expected: String,
+        actual: String,
+    },
+    IncompatibleRtop {
+        plugin: String,
+        requirement: String,
+        version: String,
+    },
+    UserAbort,
+}
+
+impl Display for RtpmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Git(error) => write!(f, "A git error occurred: {}", error.message()),
+            Self::Network(error) => write!(f, "A network error occurred: {}", error),
+            Self::ManifestParse { path, error } => {
+                write!(f, "The file {} is malformed ({}).", path.display(), error)
+            }
+            Self::Build { plugin, message } => write!(
+                f,
+                "An error occurred during the compilation of the plugin {}: {}",
+                plugin, message
+            ),
+            Self::ConfigIo { path, error } => write!(
+                f,
+                "An error occurred while accessing the {} file ({}).",
+                path.display(),
+                error
+            ),
+            Self::Io(error) => write!(f, "A filesystem error occurred: {}", error),
+            Self::NotFound(message) | Self::InvalidInput(message) => write!(f, "{}", message),
+            Self::Locked(path) => write!(
+                f,
+                "Another rtpm is running (lock held on {}), try again later or use --wait.",
+                path.display()
+            ),
+            Self::Offline(operation) => write!(
+                f,
+                "{} requires network access, which is disabled by --offline.",
+                operation
+            ),
+            Self::Dependency(message) => write!(f, "Dependency error: {}", message),
+            Self::IncompatibleRtop {
+                plugin,
+                requirement,
+                version,
+            } => write!(
+                f,
+                "The plugin {} requires Rtop {} but Rtop v{} is installed.",
+                plugin, requirement, version
+            ),
+            Self::Signature {
+                repository,
+                message,
+            } => write!(
+                f,
+                "The signature of the repository {} can't be verified: {}.",
+                repository, message
+            ),
+            Self::ChecksumMismatch {
+                subject,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The integrity check of {} failed: expected {} but got {}.",
+                subject, expected, actual
+            ),
+            Self::UserAbort => write!(f, "Operation aborted by the user."),
+        }
+    }
+}
+
+impl std::error::Error for RtpmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Git(error) => Some(error),
+            Self::Network(error) => Some(error),
+            Self::ManifestParse { error, .. } => Some(error),
+            Self::ConfigIo { error, .. } | Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<git2::Error> for RtpmError {
+    fn from(error: git2::Error) -> Self {
+        Self::Git(error)
+    }
+}
+
+impl From<reqwest::Error> for RtpmError {
+    fn from(error: reqwest::Error) -> Self {
+        Self::Network(error)
+    }
+}
+
+impl From<std::io::Error> for RtpmError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
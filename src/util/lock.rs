@@ -0,0 +1,41 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use colored::Colorize;
+use fs2::FileExt;
+use std::fs::File;
+use std::path::PathBuf;
+
+pub struct RtpmLock {
+    file: File,
+}
+
+impl RtpmLock {
+    pub fn acquire(paths: &Paths, wait: bool) -> Result<Self, RtpmError> {
+        let lock_path: PathBuf = paths.lock_file();
+        let file: File = File::create(&lock_path).map_err(|error| RtpmError::ConfigIo {
+            path: lock_path.clone(),
+            error,
+        })?;
+
+        if file.try_lock_exclusive().is_err() {
+            if !wait {
+                return Err(RtpmError::Locked(lock_path));
+            }
+            println!(
+                ":: {}",
+                "Another rtpm is running, waiting for it to finish...".yellow()
+            );
+            file.lock_exclusive().map_err(|error| RtpmError::ConfigIo {
+                path: lock_path,
+                error,
+            })?;
+        }
+        Ok(Self { file })
+    }
+}
+
+impl Drop for RtpmLock {
+    fn drop(&mut self) {
+        drop(self.file.unlock());
+    }
+}
@@ -1,2 +1,12 @@
+pub mod artifact;
+pub mod build;
+pub mod error;
+pub mod lock;
+pub mod network;
+pub mod paths;
+pub mod platform;
+pub mod progress;
+pub mod resolver;
+pub mod signature;
 pub mod structs;
 pub mod utils;
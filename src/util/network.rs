@@ -0,0 +1,221 @@
+use crate::git::credentials::url_host;
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::structs::{NetworkConfig, RTPMConfig};
+use crate::util::utils::{read_json_file, set_once};
+use git2::ProxyOptions;
+use reqwest::blocking::{Client, ClientBuilder};
+use reqwest::{Certificate, NoProxy, Proxy};
+use std::ffi::{c_char, c_int, CString};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Loaded once by `main` with the environment applied, like the git credentials.
+static NETWORK: OnceLock<NetworkConfig> = OnceLock::new();
+// `http.sslCAInfo` of the git command replaces the system certificates, they are bundled with the extra ones.
+static GIT_CA_BUNDLE: OnceLock<PathBuf> = OnceLock::new();
+
+const SYSTEM_CA_BUNDLES: [&str; 5] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+    "/usr/local/etc/openssl/cert.pem",
+];
+
+pub fn load_network_config(paths: &Paths, offline: bool) -> Result<(), RtpmError> {
+    let rtpm_config: RTPMConfig = read_json_file(&paths.rtpm_config())?;
+    let mut network_config: NetworkConfig = rtpm_config.network.with_environment();
+    network_config.offline |= offline;
+    for certificate in &network_config.ca_certificates {
+        add_git_certificate(certificate)?;
+    }
+    if !network_config.ca_certificates.is_empty() {
+        set_once(
+            &GIT_CA_BUNDLE,
+            write_ca_bundle(
+                &network_config.ca_certificates,
+                &paths.cache().join("ca-bundle.pem"),
+            )?,
+            "network",
+        )?;
+    }
+    set_once(&NETWORK, network_config, "network")?;
+    Ok(())
+}
+
+fn network_config() -> &'static NetworkConfig {
+    NETWORK.get_or_init(|| NetworkConfig::default().with_environment())
+}
+
+impl NetworkConfig {
+    // The rtpm config wins over `HTTPS_PROXY`/`ALL_PROXY`/`HTTP_PROXY` and `NO_PROXY`.
+    pub fn with_environment(mut self) -> Self {
+        let environment = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        };
+        if self.proxy.is_none() {
+            self.proxy = environment(&[
+                "HTTPS_PROXY",
+                "https_proxy",
+                "ALL_PROXY",
+                "all_proxy",
+                "HTTP_PROXY",
+                "http_proxy",
+            ]);
+        }
+        if self.no_proxy.is_none() {
+            self.no_proxy = environment(&["NO_PROXY", "no_proxy"]);
+        }
+        self
+    }
+
+    pub fn proxy_for(&self, url: &str) -> Option<&str> {
+        let proxy: &str = self.proxy.as_deref()?;
+        let host: String = url_host(url)?;
+        let bypassed: bool = self.no_proxy.as_deref().is_some_and(|no_proxy| {
+            no_proxy
+                .split(',')
+                .map(|entry| entry.trim().trim_start_matches('.'))
+                .filter(|entry| !entry.is_empty())
+                .any(|entry| {
+                    entry == "*" || host == entry || host.ends_with(&format!(".{}", entry))
+                })
+        });
+        if bypassed {
+            None
+        } else {
+            Some(proxy)
+        }
+    }
+}
+
+pub fn clone_depth() -> u32 {
+    network_config().clone_depth.unwrap_or(1)
+}
+
+pub fn is_offline() -> bool {
+    network_config().offline
+}
+
+pub fn ensure_online(operation: &str) -> Result<(), RtpmError> {
+    if is_offline() {
+        return Err(RtpmError::Offline(operation.to_owned()));
+    }
+    Ok(())
+}
+
+// Local paths and `file://` URLs stay usable offline.
+pub fn ensure_reachable(url: &str) -> Result<(), RtpmError> {
+    if url.starts_with("file://") || url_host(url).is_none() {
+        return Ok(());
+    }
+    ensure_online(&format!("Accessing {}", url))
+}
+
+pub fn proxy_for(url: &str) -> Option<&'static str> {
+    network_config().proxy_for(url)
+}
+
+pub fn git_proxy_options<'a>(url: &str) -> ProxyOptions<'a> {
+    let mut proxy_options: ProxyOptions = ProxyOptions::new();
+    if let Some(proxy) = proxy_for(url) {
+        proxy_options.url(proxy);
+    }
+    proxy_options
+}
+
+pub fn http_client() -> Result<Client, RtpmError> {
+    ensure_online("Downloading over HTTP")?;
+    let network: &NetworkConfig = network_config();
+    let mut builder: ClientBuilder = Client::builder();
+    builder = if let Some(proxy) = &network.proxy {
+        builder.proxy(
+            Proxy::all(proxy)?.no_proxy(network.no_proxy.as_deref().and_then(NoProxy::from_string)),
+        )
+    } else {
+        builder.no_proxy()
+    };
+    for path in &network.ca_certificates {
+        for certificate in read_certificates(path)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+// A CA file may contain a whole bundle, reqwest only parses one certificate at a time.
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, RtpmError> {
+    let content: String = std::fs::read_to_string(path).map_err(|error| RtpmError::ConfigIo {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let end_marker: &str = "-----END CERTIFICATE-----";
+    let mut certificates: Vec<Certificate> = Vec::new();
+    for block in content.split_inclusive(end_marker) {
+        if block.contains(end_marker) {
+            certificates.push(Certificate::from_pem(block.trim().as_bytes())?);
+        }
+    }
+    Ok(certificates)
+}
+
+pub fn git_ca_bundle() -> Option<&'static Path> {
+    GIT_CA_BUNDLE.get().map(PathBuf::as_path)
+}
+
+pub fn write_ca_bundle(certificates: &[PathBuf], bundle_path: &Path) -> Result<PathBuf, RtpmError> {
+    let system_bundle: Option<PathBuf> = std::env::var_os("SSL_CERT_FILE")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(SYSTEM_CA_BUNDLES.iter().map(PathBuf::from))
+        .find(|path| path.is_file());
+    let mut bundle: String = String::new();
+    for path in certificates.iter().chain(system_bundle.iter()) {
+        let content: String =
+            std::fs::read_to_string(path).map_err(|error| RtpmError::ConfigIo {
+                path: path.clone(),
+                error,
+            })?;
+        bundle.push_str(content.trim_end());
+        bundle.push('\n');
+    }
+    if let Some(parent) = bundle_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(bundle_path, bundle)?;
+    Ok(bundle_path.to_path_buf())
+}
+
+// git2 doesn't expose this option, the certificates are added to the default store of libgit2.
+fn add_git_certificate(path: &Path) -> Result<(), RtpmError> {
+    let absolute_path: PathBuf =
+        std::fs::canonicalize(path).map_err(|error| RtpmError::ConfigIo {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    let file: CString =
+        CString::new(absolute_path.to_string_lossy().into_owned()).map_err(|_| {
+            RtpmError::InvalidInput(format!(
+                "The certificate path {} is invalid.",
+                absolute_path.display()
+            ))
+        })?;
+    libgit2_sys::init();
+    // SAFETY: both pointers are valid C strings or null for the duration of the call.
+    let code: c_int = unsafe {
+        libgit2_sys::git_libgit2_opts(
+            libgit2_sys::GIT_OPT_SET_SSL_CERT_LOCATIONS as c_int,
+            file.as_ptr(),
+            std::ptr::null::<c_char>(),
+        )
+    };
+    if code < 0 {
+        return Err(git2::Error::last_error(code)
+            .unwrap_or_else(|| git2::Error::from_str("unable to load the certificate"))
+            .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use crate::util::error::RtpmError;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+}
+
+impl Paths {
+    pub fn new(data_dir: PathBuf, config_dir: PathBuf) -> Self {
+        Self {
+            data_dir,
+            config_dir,
+        }
+    }
+
+    // Priority: `--root`, then `RTPM_DATA_DIR`/`RTPM_CONFIG_DIR`, then `RTPM_HOME`, then the system directories.
+    pub fn resolve(root: Option<&Path>) -> Result<Self, RtpmError> {
+        if let Some(root_path) = root {
+            return Ok(Self::new(root_path.to_path_buf(), root_path.to_path_buf()));
+        }
+        let home: Option<PathBuf> = std::env::var_os("RTPM_HOME").map(PathBuf::from);
+
+        let data_dir: PathBuf = if let Some(data_dir) = std::env::var_os("RTPM_DATA_DIR") {
+            PathBuf::from(data_dir)
+        } else if let Some(home_path) = home.clone() {
+            home_path
+        } else {
+            dirs::data_dir()
+                .ok_or_else(unsupported_system)?
+                .join("rtop")
+        };
+        let config_dir: PathBuf = if let Some(config_dir) = std::env::var_os("RTPM_CONFIG_DIR") {
+            PathBuf::from(config_dir)
+        } else if let Some(home_path) = home {
+            home_path
+        } else {
+            dirs::config_dir()
+                .ok_or_else(unsupported_system)?
+                .join("rtop")
+        };
+        Ok(Self::new(data_dir, config_dir))
+    }
+
+    pub fn plugins(&self) -> PathBuf {
+        self.data_dir.join("plugins")
+    }
+
+    pub fn plugin(&self, id: &str) -> PathBuf {
+        self.plugins().join(id)
+    }
+
+    pub fn repositories(&self) -> PathBuf {
+        self.data_dir.join("repositories")
+    }
+
+    pub fn repository(&self, id: &str) -> PathBuf {
+        self.repositories().join(id)
+    }
+
+    pub fn staging(&self) -> PathBuf {
+        self.data_dir.join("staging")
+    }
+
+    pub fn cache(&self) -> PathBuf {
+        self.data_dir.join("cache")
+    }
+
+    pub fn source_caches(&self) -> PathBuf {
+        self.cache().join("sources")
+    }
+
+    pub fn source_cache(&self, id: &str) -> PathBuf {
+        self.source_caches().join(id)
+    }
+
+    pub fn build_cache(&self) -> PathBuf {
+        self.cache().join("target")
+    }
+
+    pub fn logs(&self) -> PathBuf {
+        self.data_dir.join("logs")
+    }
+
+    pub fn build_log(&self, id: &str) -> PathBuf {
+        self.logs().join(format!("{}.log", id))
+    }
+
+    pub fn failed_build(&self, id: &str) -> PathBuf {
+        self.data_dir.join("failed").join(id)
+    }
+
+    pub fn lock_file(&self) -> PathBuf {
+        self.config_dir.join("rtpm.lck")
+    }
+
+    pub fn rtpm_config(&self) -> PathBuf {
+        self.config_dir.join("rtpm.json")
+    }
+
+    pub fn credentials(&self) -> PathBuf {
+        self.config_dir.join("credentials.json")
+    }
+
+    pub fn rtop_config(&self) -> PathBuf {
+        self.config_dir.join("config.json")
+    }
+}
+
+fn unsupported_system() -> RtpmError {
+    RtpmError::NotFound("Your system is not supported, please open an issue at: https://github.com/RtopRS/RtopPluginManager/issues/new so we can add support for your system.".to_owned())
+}
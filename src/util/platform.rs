@@ -0,0 +1,322 @@
+use crate::util::structs::PluginManifest;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub os: String,
+    pub arch: String,
+    pub family: String,
+    pub env: String,
+    pub pointer_width: String,
+}
+
+impl Host {
+    pub fn current() -> Self {
+        let env: &str = if cfg!(target_env = "gnu") {
+            "gnu"
+        } else if cfg!(target_env = "musl") {
+            "musl"
+        } else if cfg!(target_env = "msvc") {
+            "msvc"
+        } else {
+            ""
+        };
+        Self {
+            os: std::env::consts::OS.to_owned(),
+            arch: std::env::consts::ARCH.to_owned(),
+            family: std::env::consts::FAMILY.to_owned(),
+            env: env.to_owned(),
+            pointer_width: (usize::BITS).to_string(),
+        }
+    }
+
+    fn value(&self, key: &str) -> Option<&str> {
+        match key {
+            "target_os" => Some(&self.os),
+            "target_arch" => Some(&self.arch),
+            "target_family" => Some(&self.family),
+            "target_env" => Some(&self.env),
+            "target_pointer_width" => Some(&self.pointer_width),
+            _ => None,
+        }
+    }
+}
+
+// Checks the legacy `os`/`arch` lists and the `platforms` rules, every present constraint must match.
+pub fn verify_platform(plugin_manifest: &PluginManifest, host: &Host) -> Result<(), String> {
+    if let Some(os) = plugin_manifest.os.as_ref().filter(|os| !os.is_empty()) {
+        if !os.contains(&host.os) {
+            return Err(format!(
+                "the OS {} is not in the supported list ({})",
+                host.os,
+                os.join(", ")
+            ));
+        }
+    }
+    if let Some(arch) = plugin_manifest
+        .arch
+        .as_ref()
+        .filter(|arch| !arch.is_empty())
+    {
+        if !arch.contains(&host.arch) {
+            return Err(format!(
+                "the architecture {} is not in the supported list ({})",
+                host.arch,
+                arch.join(", ")
+            ));
+        }
+    }
+    if let Some(platforms) = plugin_manifest
+        .platforms
+        .as_ref()
+        .filter(|platforms| !platforms.is_empty())
+    {
+        let mut reasons: Vec<String> = Vec::new();
+        for platform in platforms {
+            match matches_platform(platform, host) {
+                Ok(()) => return Ok(()),
+                Err(reason) => reasons.push(reason),
+            }
+        }
+        return Err(format!(
+            "no platform matches {}-{}{} ({})",
+            host.arch,
+            host.os,
+            if host.env.is_empty() {
+                String::new()
+            } else {
+                format!("-{}", host.env)
+            },
+            reasons.join("; ")
+        ));
+    }
+    Ok(())
+}
+
+// A platform is either a Rust target triple (the vendor is ignored) or a `cfg(...)` expression.
+pub fn matches_platform(platform: &str, host: &Host) -> Result<(), String> {
+    let trimmed: &str = platform.trim();
+    if let Some(expression) = trimmed
+        .strip_prefix("cfg(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let mut parser: CfgParser = CfgParser {
+            chars: expression.chars().peekable(),
+        };
+        let predicate: CfgPredicate = parser
+            .parse_predicate()
+            .and_then(|predicate| parser.expect_end().map(|()| predicate))
+            .map_err(|error| format!("{} is not a valid cfg expression ({})", trimmed, error))?;
+        return if predicate.evaluate(host)? {
+            Ok(())
+        } else {
+            Err(format!("{} is false on this host", trimmed))
+        };
+    }
+    matches_triple(trimmed, host)
+}
+
+fn matches_triple(triple: &str, host: &Host) -> Result<(), String> {
+    let components: Vec<&str> = triple.split('-').collect();
+    let os_index: usize = components
+        .iter()
+        .position(|component| normalize_os(component).is_some())
+        .filter(|index| *index > 0)
+        .ok_or_else(|| format!("{} is not a valid target triple", triple))?;
+    let arch: &str = normalize_arch(components[0]);
+    let os: &str = normalize_os(components[os_index]).unwrap_or_default();
+    let env: Option<&str> = components.get(os_index + 1).map(|env| normalize_env(env));
+
+    if arch != host.arch {
+        return Err(format!(
+            "{}: the architecture {} does not match {}",
+            triple, arch, host.arch
+        ));
+    }
+    if os != host.os {
+        return Err(format!(
+            "{}: the OS {} does not match {}",
+            triple, os, host.os
+        ));
+    }
+    if let Some(libc) = env.filter(|libc| *libc != host.env) {
+        return Err(format!(
+            "{}: the environment {} does not match {}",
+            triple,
+            libc,
+            if host.env.is_empty() {
+                "none"
+            } else {
+                &host.env
+            }
+        ));
+    }
+    Ok(())
+}
+
+fn normalize_arch(arch: &str) -> &str {
+    match arch {
+        "i386" | "i586" | "i686" => "x86",
+        "amd64" => "x86_64",
+        "arm64" => "aarch64",
+        "riscv64gc" | "riscv64imac" => "riscv64",
+        _ if arch.starts_with("armv") || arch.starts_with("thumbv") => "arm",
+        _ => arch,
+    }
+}
+
+fn normalize_os(os: &str) -> Option<&'static str> {
+    match os {
+        "linux" => Some("linux"),
+        "windows" => Some("windows"),
+        "darwin" | "macos" => Some("macos"),
+        "ios" => Some("ios"),
+        "android" | "androideabi" => Some("android"),
+        "freebsd" => Some("freebsd"),
+        "netbsd" => Some("netbsd"),
+        "openbsd" => Some("openbsd"),
+        "dragonfly" => Some("dragonfly"),
+        "illumos" => Some("illumos"),
+        "solaris" => Some("solaris"),
+        _ => None,
+    }
+}
+
+fn normalize_env(env: &str) -> &str {
+    match env {
+        "gnueabi" | "gnueabihf" | "gnux32" => "gnu",
+        "musleabi" | "musleabihf" => "musl",
+        _ => env,
+    }
+}
+
+enum CfgPredicate {
+    All(Vec<CfgPredicate>),
+    Any(Vec<CfgPredicate>),
+    Not(Box<CfgPredicate>),
+    Flag(String),
+    KeyValue(String, String),
+}
+
+impl CfgPredicate {
+    fn evaluate(&self, host: &Host) -> Result<bool, String> {
+        match self {
+            Self::All(predicates) => {
+                for predicate in predicates {
+                    if !predicate.evaluate(host)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Self::Any(predicates) => {
+                for predicate in predicates {
+                    if predicate.evaluate(host)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Self::Not(predicate) => Ok(!predicate.evaluate(host)?),
+            Self::Flag(flag) => match flag.as_str() {
+                "unix" | "windows" => Ok(host.family == *flag),
+                _ => Err(format!("the cfg flag {} is not supported", flag)),
+            },
+            Self::KeyValue(key, value) => host
+                .value(key)
+                .map(|host_value| host_value == value)
+                .ok_or_else(|| format!("the cfg key {} is not supported", key)),
+        }
+    }
+}
+
+struct CfgParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl CfgParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected {} but found {}", expected, c)),
+            None => Err(format!("expected {}", expected)),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        self.chars
+            .peek()
+            .map_or(Ok(()), |c| Err(format!("unexpected {}", c)))
+    }
+
+    fn parse_identifier(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let mut identifier: String = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+            identifier.push(c);
+        }
+        if identifier.is_empty() {
+            return Err("expected an identifier".to_owned());
+        }
+        Ok(identifier)
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value: String = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(value),
+                Some(c) => value.push(c),
+                None => return Err("unterminated string".to_owned()),
+            }
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<CfgPredicate>, String> {
+        self.expect('(')?;
+        let mut predicates: Vec<CfgPredicate> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.chars.next_if_eq(&')').is_some() {
+                return Ok(predicates);
+            }
+            predicates.push(self.parse_predicate()?);
+            self.skip_whitespace();
+            if self.chars.next_if_eq(&',').is_none() {
+                self.expect(')')?;
+                return Ok(predicates);
+            }
+        }
+    }
+
+    fn parse_predicate(&mut self) -> Result<CfgPredicate, String> {
+        let identifier: String = self.parse_identifier()?;
+        match identifier.as_str() {
+            "all" => Ok(CfgPredicate::All(self.parse_list()?)),
+            "any" => Ok(CfgPredicate::Any(self.parse_list()?)),
+            "not" => {
+                let mut predicates: Vec<CfgPredicate> = self.parse_list()?;
+                if predicates.len() != 1 {
+                    return Err("not() takes exactly one predicate".to_owned());
+                }
+                Ok(CfgPredicate::Not(Box::new(predicates.remove(0))))
+            }
+            _ => {
+                self.skip_whitespace();
+                if self.chars.next_if_eq(&'=').is_some() {
+                    Ok(CfgPredicate::KeyValue(identifier, self.parse_string()?))
+                } else {
+                    Ok(CfgPredicate::Flag(identifier))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::util::error::RtpmError;
+use colored::{ColoredString, Colorize};
+use std::cell::RefCell;
+use std::io::{self, IsTerminal, Write};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Line {
+    label: String,
+    status: String,
+    reported: bool,
+}
+
+struct Display {
+    lines: Vec<Line>,
+    drawn: usize,
+    last_draw: Option<Instant>,
+}
+
+// One status line per item, redrawn in place on a terminal and printed line by line otherwise.
+pub struct MultiProgress {
+    display: Mutex<Display>,
+    interactive: bool,
+}
+
+thread_local! {
+    static CURRENT_LINE: RefCell<Option<(Arc<MultiProgress>, usize)>> = const { RefCell::new(None) };
+}
+
+// Set while a transient status printed outside of a `MultiProgress` is on screen.
+static PENDING_STATUS: AtomicBool = AtomicBool::new(false);
+
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+impl MultiProgress {
+    pub fn new(labels: Vec<String>) -> Arc<Self> {
+        let progress: Arc<Self> = Arc::new(Self {
+            display: Mutex::new(Display {
+                lines: labels
+                    .into_iter()
+                    .map(|label| Line {
+                        label,
+                        status: "waiting...".dimmed().to_string(),
+                        reported: false,
+                    })
+                    .collect(),
+                drawn: 0,
+                last_draw: None,
+            }),
+            interactive: io::stdout().is_terminal(),
+        });
+        if progress.interactive {
+            clear_pending_status();
+            redraw(&mut progress.lock());
+        }
+        progress
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Display> {
+        self.display.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set(&self, index: usize, status: String, persistent: bool) {
+        let mut display: MutexGuard<Display> = self.lock();
+        display.lines[index].status = status;
+        if self.interactive {
+            if persistent
+                || display
+                    .last_draw
+                    .is_none_or(|last_draw| last_draw.elapsed() >= REDRAW_INTERVAL)
+            {
+                redraw(&mut display);
+            }
+        } else if persistent {
+            println!(
+                ":: {} {}",
+                display.lines[index].label, display.lines[index].status
+            );
+        }
+    }
+
+    fn complete<R>(&self, index: usize, result: &Result<R, RtpmError>) {
+        let reported: bool = self.lock().lines[index].reported;
+        match result {
+            Ok(_) if reported => {
+                if self.interactive {
+                    redraw(&mut self.lock());
+                }
+            }
+            Ok(_) => self.set(index, "done".green().to_string(), true),
+            Err(error) => self.set(index, error.to_string().red().to_string(), true),
+        }
+    }
+}
+
+fn redraw(display: &mut Display) {
+    let mut output: String = String::new();
+    if display.drawn > 0 {
+        output.push_str(&format!("\x1b[{}A", display.drawn));
+    }
+    for line in &display.lines {
+        output.push_str(&format!("\r\x1b[2K:: {} {}\n", line.label, line.status));
+    }
+    print!("{}", output);
+    drop(io::stdout().flush());
+    display.drawn = display.lines.len();
+    display.last_draw = Some(Instant::now());
+}
+
+fn clear_pending_status() {
+    if PENDING_STATUS.swap(false, Ordering::SeqCst) {
+        print!("\r\x1b[2K");
+    }
+}
+
+fn report(status: &ColoredString, persistent: bool) -> bool {
+    CURRENT_LINE.with(|current_line| {
+        if let Some((progress, index)) = current_line.borrow().as_ref() {
+            progress.lock().lines[*index].reported = true;
+            progress.set(*index, status.to_string(), persistent);
+            true
+        } else {
+            false
+        }
+    })
+}
+
+// Transient status like a download percentage, overwritten by the next one.
+pub fn update(status: &str) {
+    if report(&status.green(), false) || !io::stdout().is_terminal() {
+        return;
+    }
+    print!("\r\x1b[2K:: {}", status.green());
+    drop(io::stdout().flush());
+    PENDING_STATUS.store(true, Ordering::SeqCst);
+}
+
+pub fn notice(message: &str) {
+    if !report(&message.green(), true) {
+        clear_pending_status();
+        println!(":: {}", message.green());
+    }
+}
+
+pub fn warn(message: &str) {
+    if !report(&message.yellow(), true) {
+        clear_pending_status();
+        println!(":: {}", message.yellow());
+    }
+}
+
+// Runs `work` on at most `jobs` threads, the progress of each item is shown on its own line.
+pub fn run_parallel<T, R, F>(
+    items: &[(String, T)],
+    jobs: usize,
+    work: F,
+) -> Vec<Result<R, RtpmError>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, RtpmError> + Sync,
+{
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let progress: Arc<MultiProgress> = MultiProgress::new(
+        items
+            .iter()
+            .map(|(label, _)| label.bold().to_string())
+            .collect(),
+    );
+    let next_item: AtomicUsize = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<R, RtpmError>>>> =
+        Mutex::new(items.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index: usize = next_item.fetch_add(1, Ordering::SeqCst);
+                let item: &T = match items.get(index) {
+                    Some((_, item)) => item,
+                    None => break,
+                };
+                progress.set(index, "in progress...".green().to_string(), false);
+                CURRENT_LINE.with(|current_line| {
+                    *current_line.borrow_mut() = Some((Arc::clone(&progress), index));
+                });
+                let result: Result<R, RtpmError> = work(item);
+                CURRENT_LINE.with(|current_line| current_line.borrow_mut().take());
+                progress.complete(index, &result);
+                results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+            });
+        }
+    });
+    println!();
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| {
+                Err(RtpmError::InvalidInput(
+                    "The operation was interrupted.".to_owned(),
+                ))
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,270 @@
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::signature::signed_repository;
+use crate::util::structs::{
+    PluginManifest, RTPMConfig, RTPMConfigPluginElement, RepositoryPlugin, RepositoryPluginEntry,
+};
+use crate::util::utils::{parse_version, read_json_file, sha256_hex, verify_repository_entry};
+use semver::VersionReq;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Requirement {
+    pub id: String,
+    pub range: String,
+    pub required_by: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPlugin {
+    pub id: String,
+    pub version: String,
+    pub repository: String,
+    pub manifest_hash: String,
+    pub expected_commit: Option<String>,
+    pub(crate) manifest: PluginManifest,
+}
+
+struct Resolver<'a> {
+    paths: &'a Paths,
+    rtpm_config: &'a RTPMConfig,
+    installed: BTreeMap<String, &'a RTPMConfigPluginElement>,
+    candidates: BTreeMap<String, Vec<ResolvedPlugin>>,
+    failure: Option<String>,
+}
+
+// Returns the plugins to install, each one after its dependencies. Installed plugins are kept as they are.
+pub fn resolve_dependencies(
+    requirements: &[Requirement],
+    rtpm_config: &RTPMConfig,
+    paths: &Paths,
+) -> Result<Vec<ResolvedPlugin>, RtpmError> {
+    let mut resolver: Resolver = Resolver {
+        paths,
+        rtpm_config,
+        installed: rtpm_config
+            .plugins
+            .iter()
+            .map(|element| (element.id.clone(), element))
+            .collect(),
+        candidates: BTreeMap::new(),
+        failure: None,
+    };
+    let mut selected: BTreeMap<String, ResolvedPlugin> = BTreeMap::new();
+    if !resolver.solve(requirements, &mut selected)? {
+        return Err(RtpmError::Dependency(resolver.failure.unwrap_or_else(
+            || "Unable to find a set of plugins satisfying every requirement.".to_owned(),
+        )));
+    }
+
+    let mut ordered: Vec<ResolvedPlugin> = Vec::new();
+    let mut visited: BTreeSet<String> = BTreeSet::new();
+    for requirement in requirements {
+        visit(&requirement.id, &mut selected, &mut visited, &mut ordered);
+    }
+    Ok(ordered)
+}
+
+fn visit(
+    id: &str,
+    selected: &mut BTreeMap<String, ResolvedPlugin>,
+    visited: &mut BTreeSet<String>,
+    ordered: &mut Vec<ResolvedPlugin>,
+) {
+    if !visited.insert(id.to_owned()) {
+        return;
+    }
+    let dependencies: Vec<String> = if let Some(plugin) = selected.get(id) {
+        plugin.manifest.dependencies.keys().cloned().collect()
+    } else {
+        return;
+    };
+    for dependency in dependencies {
+        visit(&dependency, selected, visited, ordered);
+    }
+    if let Some(plugin) = selected.remove(id) {
+        ordered.push(plugin);
+    }
+}
+
+fn parse_range(range: &str) -> Result<VersionReq, RtpmError> {
+    if range.trim().is_empty() {
+        return Ok(VersionReq::STAR);
+    }
+    VersionReq::parse(range).map_err(|error| {
+        RtpmError::Dependency(format!("Invalid version range {} ({}).", range, error))
+    })
+}
+
+fn satisfies(version: &str, range: &str) -> Result<bool, RtpmError> {
+    let requirement: VersionReq = parse_range(range)?;
+    if requirement == VersionReq::STAR {
+        return Ok(true);
+    }
+    Ok(parse_version(version).is_some_and(|parsed| requirement.matches(&parsed)))
+}
+
+fn describe(requirement: &Requirement) -> String {
+    requirement.required_by.as_ref().map_or_else(
+        || format!("{} {}", requirement.id, requirement.range),
+        |required_by| {
+            format!(
+                "{} {} (required by {})",
+                requirement.id, requirement.range, required_by
+            )
+        },
+    )
+}
+
+impl Resolver<'_> {
+    fn load_candidates(&mut self, id: &str) -> Result<Vec<ResolvedPlugin>, RtpmError> {
+        if let Some(candidates) = self.candidates.get(id) {
+            return Ok(candidates.clone());
+        }
+        let mut candidates: Vec<ResolvedPlugin> = Vec::new();
+        for repository in &self.rtpm_config.repositories {
+            let repository_path: PathBuf = self.paths.repository(repository);
+            if !repository_path.exists() {
+                continue;
+            }
+            let repository_plugins: RepositoryPlugin =
+                read_json_file(&repository_path.join("plugins.json"))?;
+            let entry: &RepositoryPluginEntry = if let Some(entry) = repository_plugins.entry(id) {
+                entry
+            } else {
+                continue;
+            };
+            let manifest_path: PathBuf =
+                repository_path.join("plugins").join(format!("{}.json", id));
+            let manifest: PluginManifest = read_json_file(&manifest_path)?;
+            let manifest_hash: String = sha256_hex(&std::fs::read(&manifest_path)?);
+            verify_repository_entry(
+                entry,
+                &manifest,
+                &manifest_hash,
+                signed_repository(self.rtpm_config, repository),
+            )?;
+            candidates.push(ResolvedPlugin {
+                id: manifest.id.clone(),
+                version: manifest.version.clone(),
+                repository: repository.clone(),
+                manifest_hash,
+                expected_commit: entry.commit.clone(),
+                manifest,
+            });
+        }
+        self.candidates.insert(id.to_owned(), candidates.clone());
+        Ok(candidates)
+    }
+
+    fn conflicts_with(
+        &self,
+        candidate: &ResolvedPlugin,
+        selected: &BTreeMap<String, ResolvedPlugin>,
+    ) -> Option<String> {
+        let installed = self
+            .installed
+            .values()
+            .map(|element| (&element.id, &element.conflicts));
+        let chosen = selected
+            .values()
+            .map(|plugin| (&plugin.id, &plugin.manifest.conflicts));
+        for (id, conflicts) in installed.chain(chosen) {
+            if candidate.manifest.conflicts.contains(id) || conflicts.contains(&candidate.id) {
+                return Some(id.clone());
+            }
+        }
+        None
+    }
+
+    fn solve(
+        &mut self,
+        requirements: &[Requirement],
+        selected: &mut BTreeMap<String, ResolvedPlugin>,
+    ) -> Result<bool, RtpmError> {
+        let mut unresolved: Option<String> = None;
+        for requirement in requirements {
+            let version: &str = if let Some(element) = self.installed.get(&requirement.id) {
+                &element.version
+            } else if let Some(plugin) = selected.get(&requirement.id) {
+                &plugin.version
+            } else {
+                if unresolved.is_none() {
+                    unresolved = Some(requirement.id.clone());
+                }
+                continue;
+            };
+            if !satisfies(version, &requirement.range)? {
+                self.failure = Some(format!(
+                    "{} v{} does not match {}.",
+                    requirement.id,
+                    version,
+                    describe(requirement)
+                ));
+                return Ok(false);
+            }
+        }
+        let id: String = if let Some(id) = unresolved {
+            id
+        } else {
+            return Ok(true);
+        };
+
+        let candidates: Vec<ResolvedPlugin> = self.load_candidates(&id)?;
+        if candidates.is_empty() {
+            let requirement: Option<&Requirement> = requirements.iter().find(|r| r.id == id);
+            self.failure = Some(format!(
+                "The plugin {} was not found in any repository{}.",
+                id,
+                requirement
+                    .and_then(|r| r.required_by.as_ref())
+                    .map_or_else(String::new, |required_by| format!(
+                        " (required by {})",
+                        required_by
+                    ))
+            ));
+            return Ok(false);
+        }
+        for candidate in candidates {
+            let mut matches_all: bool = true;
+            for requirement in requirements.iter().filter(|r| r.id == id) {
+                if !satisfies(&candidate.version, &requirement.range)? {
+                    self.failure = Some(format!(
+                        "No version of {} matches {}.",
+                        id,
+                        describe(requirement)
+                    ));
+                    matches_all = false;
+                    break;
+                }
+            }
+            if !matches_all {
+                continue;
+            }
+            if let Some(conflicting) = self.conflicts_with(&candidate, selected) {
+                self.failure = Some(format!(
+                    "The plugin {} conflicts with the plugin {}.",
+                    id, conflicting
+                ));
+                continue;
+            }
+
+            let mut next_requirements: Vec<Requirement> = requirements.to_vec();
+            for (dependency, range) in &candidate.manifest.dependencies {
+                parse_range(range)?;
+                next_requirements.push(Requirement {
+                    id: dependency.clone(),
+                    range: range.clone(),
+                    required_by: Some(id.clone()),
+                });
+            }
+            selected.insert(id.clone(), candidate);
+            if self.solve(&next_requirements, selected)? {
+                return Ok(true);
+            }
+            selected.remove(&id);
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::util::error::RtpmError;
+use crate::util::structs::{RTPMConfig, RTPMConfigRepositoryElement, RepositoryManifest};
+use crate::util::utils::read_json_file;
+use colored::Colorize;
+use minisign_verify::{PublicKey, Signature};
+use std::path::Path;
+
+pub const INDEX_FILE: &str = "plugins.json";
+pub const INDEX_SIGNATURE_FILE: &str = "plugins.json.minisig";
+
+pub fn verify_index(
+    repository: &str,
+    index: &[u8],
+    signature: &str,
+    public_key: &str,
+) -> Result<(), RtpmError> {
+    let signature_error = |message: String| RtpmError::Signature {
+        repository: repository.to_owned(),
+        message,
+    };
+    let key: PublicKey = PublicKey::from_base64(public_key.trim())
+        .map_err(|error| signature_error(format!("invalid public key ({})", error)))?;
+    let decoded_signature: Signature = Signature::decode(signature)
+        .map_err(|error| signature_error(format!("invalid signature file ({})", error)))?;
+    key.verify(index, &decoded_signature, false)
+        .map_err(|error| signature_error(format!("{} doesn't match ({})", INDEX_FILE, error)))
+}
+
+pub fn signed_repository<'a>(rtpm_config: &RTPMConfig, repository: &'a str) -> Option<&'a str> {
+    rtpm_config
+        .repository_details
+        .get(repository)
+        .and_then(|details| details.public_key.as_ref())
+        .map(|_| repository)
+}
+
+// Pins the key declared by the repository on first use, then requires every index to be signed by it.
+pub fn check_repository_signature(
+    repository: &str,
+    manifest_key: Option<&str>,
+    index: &[u8],
+    signature: Option<&str>,
+    details: &mut RTPMConfigRepositoryElement,
+) -> Result<(), RtpmError> {
+    let pinned_key: String = match (&details.public_key, manifest_key) {
+        (None, None) => return Ok(()),
+        (Some(pinned_key), Some(key)) if pinned_key.trim() != key.trim() => {
+            return Err(RtpmError::Signature {
+                repository: repository.to_owned(),
+                message: format!(
+                    "the repository key changed from {} to {}, remove and add the repository again if this is expected",
+                    pinned_key, key
+                ),
+            })
+        }
+        (Some(pinned_key), _) => pinned_key.clone(),
+        (None, Some(key)) => key.to_owned(),
+    };
+    let index_signature: &str = signature.ok_or_else(|| RtpmError::Signature {
+        repository: repository.to_owned(),
+        message: format!("{} is missing", INDEX_SIGNATURE_FILE),
+    })?;
+    verify_index(repository, index, index_signature, &pinned_key)?;
+
+    if details.public_key.is_none() {
+        println!(
+            ":: {}",
+            format!(
+                "Trusting the key {} of the repository {} on first use.",
+                pinned_key, repository
+            )
+            .yellow()
+        );
+        details.public_key = Some(pinned_key);
+    }
+    Ok(())
+}
+
+// Same check on a repository freshly cloned to `repository_path`.
+pub fn check_cloned_repository_signature(
+    repository: &str,
+    repository_path: &Path,
+    details: &mut RTPMConfigRepositoryElement,
+) -> Result<(), RtpmError> {
+    let repository_manifest: RepositoryManifest =
+        read_json_file(&repository_path.join("manifest.json"))?;
+    let index: Vec<u8> = std::fs::read(repository_path.join(INDEX_FILE)).unwrap_or_default();
+    let signature: Option<String> =
+        std::fs::read_to_string(repository_path.join(INDEX_SIGNATURE_FILE)).ok();
+    check_repository_signature(
+        repository,
+        repository_manifest.public_key.as_deref(),
+        &index,
+        signature.as_deref(),
+        details,
+    )
+}
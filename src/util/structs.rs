@@ -1,17 +1,50 @@
-use serde::{Deserialize, Serialize};
+use crate::git::shallow::is_object_id;
+use crate::util::utils::is_plugin_id;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct PluginManifest {
+    #[serde(deserialize_with = "plugin_id")]
     pub(crate) id: String,
     pub(crate) name: String,
     pub(crate) description: String,
     pub(crate) version: String,
+    #[serde(deserialize_with = "git_argument")]
     pub(crate) url: String,
     pub(crate) author: Option<String>,
     pub(crate) authors: Option<Vec<String>>,
     pub(crate) license: Option<String>,
     pub(crate) os: Option<Vec<String>>,
     pub(crate) arch: Option<Vec<String>>,
+    // Target triples or `cfg(...)` expressions, the plugin supports the host if one of them matches.
+    #[serde(default)]
+    pub(crate) platforms: Option<Vec<String>>,
+    // Plugin id -> semver range.
+    #[serde(default, deserialize_with = "plugin_dependencies")]
+    pub(crate) dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    pub(crate) conflicts: Vec<String>,
+    // Semver range of the compatible Rtop versions.
+    #[serde(default)]
+    pub(crate) rtop: Option<String>,
+    // Target triple or `cfg(...)` expression -> prebuilt shared library.
+    #[serde(default)]
+    pub(crate) artifacts: BTreeMap<String, PluginArtifact>,
+    // Cargo features enabled when the user doesn't choose them with `--features`.
+    #[serde(default)]
+    pub(crate) features: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PluginArtifact {
+    pub(crate) url: Option<String>,
+    // Relative to the repository containing the manifest.
+    pub(crate) path: Option<String>,
+    pub(crate) sha256: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,32 +52,248 @@ pub struct RepositoryManifest {
     pub(crate) id: String,
     pub(crate) name: String,
     pub(crate) description: String,
+    #[serde(deserialize_with = "git_argument")]
     pub(crate) url: String,
-    // Not currently supported.
-    pub(crate) fallback_url: Option<String>,
+    // Mirrors tried in order when `url` is unreachable.
+    #[serde(default, deserialize_with = "fallback_urls")]
+    pub(crate) fallback_url: Option<FallbackUrl>,
+    // Minisign public key signing `plugins.json` (as `plugins.json.minisig`).
+    #[serde(default)]
+    pub(crate) public_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FallbackUrl {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl FallbackUrl {
+    fn urls(&self) -> &[String] {
+        match self {
+            Self::Single(url) => std::slice::from_ref(url),
+            Self::List(urls) => urls,
+        }
+    }
+}
+
+fn invalid_plugin_id(id: &str) -> String {
+    format!("{} is not a valid plugin id", id)
+}
+
+fn plugin_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let id: String = String::deserialize(deserializer)?;
+    if !is_plugin_id(&id) {
+        return Err(D::Error::custom(invalid_plugin_id(&id)));
+    }
+    Ok(id)
+}
+
+fn plugin_dependencies<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    let dependencies: BTreeMap<String, String> = BTreeMap::deserialize(deserializer)?;
+    if let Some(id) = dependencies.keys().find(|id| !is_plugin_id(id)) {
+        return Err(D::Error::custom(invalid_plugin_id(id)));
+    }
+    Ok(dependencies)
+}
+
+// The URLs are given to the git command, a value starting with `-` would be read as an option.
+fn is_git_option(value: &str) -> bool {
+    value.trim_start().starts_with('-')
+}
+
+fn git_argument<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value: String = String::deserialize(deserializer)?;
+    if is_git_option(&value) {
+        return Err(D::Error::custom(format!(
+            "{} is not a valid git URL or reference",
+            value
+        )));
+    }
+    Ok(value)
+}
+
+fn fallback_urls<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<FallbackUrl>, D::Error> {
+    let fallback_url: Option<FallbackUrl> = Option::deserialize(deserializer)?;
+    if let Some(url) = fallback_url
+        .iter()
+        .flat_map(FallbackUrl::urls)
+        .find(|url| is_git_option(url))
+    {
+        return Err(D::Error::custom(format!("{} is not a valid git URL", url)));
+    }
+    Ok(fallback_url)
+}
+
+impl RepositoryManifest {
+    pub fn mirrors(&self) -> Vec<String> {
+        let fallback_urls: &[String] = self.fallback_url.as_ref().map_or(&[], FallbackUrl::urls);
+        let mut mirrors: Vec<String> = Vec::new();
+        for url in std::iter::once(&self.url).chain(fallback_urls) {
+            let trimmed: &str = url.trim();
+            if !trimmed.is_empty() && !mirrors.iter().any(|mirror| mirror == trimmed) {
+                mirrors.push(trimmed.to_owned());
+            }
+        }
+        mirrors
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RepositoryPlugin {
-    pub(crate) plugins: Vec<String>,
+    pub(crate) plugins: Vec<RepositoryPluginEntry>,
+}
+
+impl RepositoryPlugin {
+    pub fn entry(&self, id: &str) -> Option<&RepositoryPluginEntry> {
+        self.plugins.iter().find(|entry| entry.id == id)
+    }
+}
+
+// An entry is either the plugin id or an object pinning the expected commit and checksums.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "RawRepositoryPluginEntry")]
+pub struct RepositoryPluginEntry {
+    pub(crate) id: String,
+    pub(crate) commit: Option<String>,
+    pub(crate) manifest_sha256: Option<String>,
+    // Target triple or `cfg(...)` expression -> sha256, as in the manifest.
+    pub(crate) artifacts: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRepositoryPluginEntry {
+    Id(String),
+    Detailed {
+        id: String,
+        #[serde(default)]
+        commit: Option<String>,
+        #[serde(default)]
+        manifest_sha256: Option<String>,
+        #[serde(default)]
+        artifacts: BTreeMap<String, String>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl TryFrom<RawRepositoryPluginEntry> for RepositoryPluginEntry {
+    type Error = String;
+
+    fn try_from(raw: RawRepositoryPluginEntry) -> Result<Self, Self::Error> {
+        let id: &str = match &raw {
+            RawRepositoryPluginEntry::Id(id) | RawRepositoryPluginEntry::Detailed { id, .. } => id,
+        };
+        if !is_plugin_id(id) {
+            return Err(invalid_plugin_id(id));
+        }
+        match raw {
+            RawRepositoryPluginEntry::Id(id) => Ok(Self {
+                id,
+                commit: None,
+                manifest_sha256: None,
+                artifacts: BTreeMap::new(),
+            }),
+            RawRepositoryPluginEntry::Detailed {
+                id,
+                commit,
+                manifest_sha256,
+                artifacts,
+            } => {
+                if let Some(invalid) = commit.as_deref().filter(|value| !is_object_id(value)) {
+                    return Err(format!(
+                        "{} is not the full id of a commit for the plugin {}",
+                        invalid, id
+                    ));
+                }
+                Ok(Self {
+                    id,
+                    commit,
+                    manifest_sha256,
+                    artifacts,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RtopConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) version: Option<String>,
     pages: Vec<Vec<String>>,
     pub(crate) plugins: Vec<RtopConfigPlugins>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RtopConfigPlugins {
     pub(crate) name: String,
     pub(crate) path: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct RTPMConfig {
     pub repositories: Vec<String>,
     pub plugins: Vec<RTPMConfigPluginElement>,
+    #[serde(default)]
+    pub repository_details: BTreeMap<String, RTPMConfigRepositoryElement>,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildConfig {
+    // Builds every plugin in the `cache/target` directory so the dependencies are compiled once.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shared_target_dir: bool,
+    // `external` when unset, `embedded` requires the `embedded-cargo` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BuildBackendKind>,
+    // The cargo of the external backend, `cargo` from the `PATH` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildBackendKind {
+    External,
+    Embedded,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    // Comma separated hosts, as in `NO_PROXY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    // PEM files trusted in addition to the system certificates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ca_certificates: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+    // History depth of the clones, 1 when unset and the full history with 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clone_depth: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RTPMConfigRepositoryElement {
+    #[serde(default)]
+    pub branch: Option<String>,
+    // Key trusted on first use, the index must stay signed by it.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    // Last URL the repository was fetched from, `url` or one of the fallback mirrors.
+    #[serde(default)]
+    pub mirror: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -54,4 +303,113 @@ pub struct RTPMConfigPluginElement {
     pub version: String,
     pub repo: String,
     pub plugin_type: i8,
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub manifest_hash: Option<String>,
+    #[serde(default)]
+    pub build_timestamp: Option<u64>,
+    #[serde(default)]
+    pub pin: Option<PluginPin>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    #[serde(default)]
+    pub rtop: Option<String>,
+    // Sha256 of the installed prebuilt artifact, `None` when built from source.
+    #[serde(default)]
+    pub artifact: Option<String>,
+    #[serde(default)]
+    pub build_options: BuildOptions,
+}
+
+// Cargo options of a plugin built from source, the upgrades rebuild it with the same ones.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_default_features: bool,
+    // `release` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    // Target triple, the host when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    // Variables like `RUSTFLAGS` overriding the environment of cargo.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl BuildOptions {
+    pub fn profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or("release")
+    }
+
+    // Where cargo puts the libraries: `<target_dir>[/<triple>]/<profile>`, `dev` being built in `debug`.
+    pub fn output_directory(&self, target_dir: &Path) -> PathBuf {
+        let mut directory: PathBuf = target_dir.to_path_buf();
+        if let Some(target) = &self.target {
+            directory.push(target);
+        }
+        directory.join(match self.profile() {
+            "dev" | "test" => "debug",
+            "bench" => "release",
+            profile => profile,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginPin {
+    Tag(String),
+    Branch(String),
+    Commit(String),
+}
+
+impl PluginPin {
+    pub fn reference(&self) -> &str {
+        match self {
+            Self::Tag(reference) | Self::Branch(reference) | Self::Commit(reference) => reference,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionChange {
+    Major,
+    Minor,
+    Patch,
+    Downgrade,
+    UpToDate,
+    Unknown,
+}
+
+impl Display for VersionChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Major => write!(f, "major"),
+            Self::Minor => write!(f, "minor"),
+            Self::Patch => write!(f, "patch"),
+            Self::Downgrade => write!(f, "downgrade"),
+            Self::UpToDate => write!(f, "up to date"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl Display for PluginPin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tag(tag) => write!(f, "tag {}", tag),
+            Self::Branch(branch) => write!(f, "branch {}", branch),
+            Self::Commit(commit) => write!(f, "commit {}", commit),
+        }
+    }
 }
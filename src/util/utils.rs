@@ -1,9 +1,21 @@
-use crate::util::structs::PluginManifest;
-use crate::util::structs::{RTPMConfig, RepositoryPlugin};
+use crate::git::shallow::check_git_argument;
+use crate::util::build::{build_backend, is_shared_library, library_name, BuildJob, BuildOutput};
+use crate::util::error::RtpmError;
+use crate::util::paths::Paths;
+use crate::util::platform::{verify_platform, Host};
+use crate::util::structs::{BuildOptions, PluginManifest, PluginPin, RtopConfig, VersionChange};
+use crate::util::structs::{RTPMConfig, RepositoryPlugin, RepositoryPluginEntry};
 use colored::Colorize;
+use itertools::Itertools;
+use semver::{Version, VersionReq};
 use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::DirEntry;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Components, Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use url::Url;
 
 // Based on the human_bytes library of Forkbomb9: https://gitlab.com/forkbomb9/human_bytes-rs.
@@ -21,79 +33,498 @@ pub fn convert_to_readable_unity<T: Into<f64>>(size: T) -> String {
     result
 }
 
-pub fn build_cargo_project(toml_path: &PathBuf) -> bool {
-    use cargo::core::{compiler::CompileMode, Workspace};
-    use cargo::ops::CompileOptions;
-    use cargo::util::interning::InternedString;
-    use cargo::Config;
+// The cargo output is written to `log_path`, it is also shown unless the build runs in parallel (`jobs` set).
+pub fn build_cargo_project(
+    toml_path: &Path,
+    target_dir: Option<&Path>,
+    build_options: &BuildOptions,
+    jobs: Option<u32>,
+    log_path: &Path,
+) -> Result<Option<PathBuf>, RtpmError> {
+    let plugin: String = toml_path
+        .parent()
+        .and_then(Path::file_name)
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let output: BuildOutput = BuildOutput::new(jobs.is_none());
+    let result: Result<Option<PathBuf>, String> = build_backend().compile(
+        &BuildJob {
+            toml_path,
+            target_dir,
+            build_options,
+            jobs,
+        },
+        &output,
+    );
+    let captured: String = output.contents();
+    if let Some(logs_path) = log_path.parent() {
+        std::fs::create_dir_all(logs_path)?;
+    }
+    std::fs::write(
+        log_path,
+        format!(
+            "Build of the plugin {} at {}\nOptions: {:?}\n\n{}{}\n",
+            plugin,
+            current_timestamp(),
+            build_options,
+            captured,
+            result.as_ref().err().map_or("", String::as_str)
+        ),
+    )?;
+    result.map_err(|error| {
+        let diagnostics: String = first_compiler_errors(&captured, 3);
+        RtpmError::Build {
+            message: if diagnostics.is_empty() {
+                format!(
+                    "{}\nThe build log is available with: rtpm logs {}",
+                    error, plugin
+                )
+            } else {
+                format!(
+                    "{}\n\n{}\n\nThe full build log is available with: rtpm logs {}",
+                    error, diagnostics, plugin
+                )
+            },
+            plugin,
+        }
+    })
+}
+
+// The first `count` `error` blocks of the compiler output, without the final "could not compile".
+pub fn first_compiler_errors(output: &str, count: usize) -> String {
+    let mut blocks: Vec<String> = Vec::new();
+    let mut current_block: Option<Vec<&str>> = None;
+    for line in output.lines() {
+        let trimmed_line: &str = line.trim_start();
+        if trimmed_line.starts_with("error") || trimmed_line.starts_with("warning") {
+            if let Some(block) = current_block.take() {
+                blocks.push(block.join("\n"));
+            }
+            if trimmed_line.starts_with("error") && !trimmed_line.contains("could not compile") {
+                current_block = Some(vec![line]);
+            }
+        } else if line.trim().is_empty()
+            || trimmed_line.starts_with("Compiling")
+            || trimmed_line.starts_with("Finished")
+        {
+            if let Some(block) = current_block.take() {
+                blocks.push(block.join("\n"));
+            }
+        } else if let Some(block) = current_block.as_mut() {
+            block.push(line);
+        }
+    }
+    if let Some(block) = current_block {
+        blocks.push(block.join("\n"));
+    }
+    blocks.into_iter().take(count).join("\n\n")
+}
+
+// With a shared target directory the library is copied to `lib/`, the build cache can then be pruned.
+pub fn build_plugin(
+    plugin_path: &Path,
+    target_dir: Option<&Path>,
+    build_options: &BuildOptions,
+    jobs: Option<u32>,
+    log_path: &Path,
+) -> Result<PathBuf, RtpmError> {
+    let plugin: String = plugin_path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let built_library: Option<PathBuf> = build_cargo_project(
+        &plugin_path.join("Cargo.toml"),
+        target_dir,
+        build_options,
+        jobs,
+        log_path,
+    )?;
+    let shared_lib_path: PathBuf = if let Some(library) = built_library {
+        library
+    } else if let Some(name) = library_name(&plugin_path.join("Cargo.toml"))? {
+        find_shared_library(
+            &build_options.output_directory(
+                &target_dir.map_or_else(|| plugin_path.join("target"), Path::to_path_buf),
+            ),
+            &name,
+        )?
+        .ok_or_else(|| RtpmError::Build {
+            plugin: plugin.clone(),
+            message: format!("no shared library named {} was produced", name),
+        })?
+    } else {
+        return Err(RtpmError::Build {
+            plugin,
+            message: "the Cargo.toml of the plugin has no package name".to_owned(),
+        });
+    };
+    if target_dir.is_none() {
+        return Ok(shared_lib_path);
+    }
+    let lib_path: PathBuf = plugin_path.join("lib");
+    std::fs::create_dir_all(&lib_path)?;
+    let stable_path: PathBuf =
+        lib_path.join(
+            shared_lib_path
+                .file_name()
+                .ok_or_else(|| RtpmError::Build {
+                    plugin,
+                    message: "no shared library was produced".to_owned(),
+                })?,
+        );
+    std::fs::copy(&shared_lib_path, &stable_path)?;
+    Ok(stable_path)
+}
+
+// Replaces the previously kept failed build of the plugin.
+pub fn keep_failed_build(build_path: &Path, failed_path: &Path) -> Result<(), RtpmError> {
+    if failed_path.exists() {
+        std::fs::remove_dir_all(failed_path)?;
+    }
+    if let Some(parent) = failed_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(build_path, failed_path)?;
+    println!(
+        ":: {}",
+        format!("The failed build was kept in {}.", failed_path.display()).yellow()
+    );
+    Ok(())
+}
+
+pub fn directory_size(path: &Path) -> Result<u64, RtpmError> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let mut size: u64 = 0;
+    for entry_result in std::fs::read_dir(path)? {
+        let entry: DirEntry = entry_result?;
+        let file_type: std::fs::FileType = entry.file_type()?;
+        if file_type.is_dir() {
+            size += directory_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+pub fn copy_directory(source: &Path, destination: &Path) -> Result<(), RtpmError> {
+    std::fs::create_dir_all(destination)?;
+    for entry_result in std::fs::read_dir(source)? {
+        let entry: DirEntry = entry_result?;
+        let destination_path: PathBuf = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_directory(&entry.path(), &destination_path)?;
+        } else {
+            std::fs::copy(entry.path(), destination_path)?;
+        }
+    }
+    Ok(())
+}
+
+// Splits `foo#branch`, `foo@tag` or `foo@<sha>` into the plugin and its pin. The pin follows the last `/` or `:`,
+// the `user@host:` of an scp-like URL is part of the URL.
+// The plugin and the revision can't start with `-`, they are given to the git command.
+pub fn parse_plugin_pin(plugin: &str) -> Result<(String, Option<PluginPin>), RtpmError> {
+    let (name, pin): (&str, Option<PluginPin>) =
+        if let Some((name, branch)) = plugin.split_once('#') {
+            (name, Some(PluginPin::Branch(branch.to_owned())))
+        } else {
+            let last_separator: usize = plugin.rfind(['/', ':']).map_or(0, |index| index + 1);
+            if let Some(index) = plugin[last_separator..].rfind('@') {
+                let (name, separator_and_revision) = plugin.split_at(last_separator + index);
+                let revision: &str = &separator_and_revision[1..];
+                let pin: PluginPin = if revision.len() >= 7
+                    && revision.len() <= 40
+                    && revision.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    PluginPin::Commit(revision.to_owned())
+                } else {
+                    PluginPin::Tag(revision.to_owned())
+                };
+                (name, Some(pin))
+            } else {
+                (plugin, None)
+            }
+        };
+    check_git_argument(name)?;
+    if let Some(reference) = pin.as_ref().map(PluginPin::reference) {
+        check_git_argument(reference)?;
+    }
+    Ok((name.to_owned(), pin))
+}
+
+// Accepts a leading `v` and missing minor/patch numbers (`v1.2` is `1.2.0`).
+pub fn parse_version(version: &str) -> Option<Version> {
+    let trimmed: &str = version.trim().trim_start_matches(['v', 'V']);
+    if let Ok(parsed) = Version::parse(trimmed) {
+        return Some(parsed);
+    }
+    let (core, suffix): (&str, &str) = trimmed
+        .find(['-', '+'])
+        .map_or((trimmed, ""), |index| trimmed.split_at(index));
+    let mut numbers: Vec<&str> = core.split('.').collect();
+    if numbers.is_empty() || numbers.len() > 3 {
+        return None;
+    }
+    numbers.resize(3, "0");
+    Version::parse(&format!("{}{}", numbers.join("."), suffix)).ok()
+}
+
+// `rtop --version` first, then the version written by Rtop in its config.
+pub fn detect_rtop_version(paths: &Paths) -> Option<Version> {
+    if let Some(version) = rtop_binary_version() {
+        return Some(version);
+    }
+    read_json_file::<RtopConfig>(&paths.rtop_config())
+        .ok()
+        .and_then(|config| config.version)
+        .and_then(|version| parse_version(&version))
+}
+
+fn rtop_binary_version() -> Option<Version> {
+    let mut child: Child = Command::new("rtop")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // An old Rtop without `--version` would start its interface, so don't wait for it forever.
+    let deadline: Instant = Instant::now() + Duration::from_secs(2);
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            _ => {
+                drop(child.kill());
+                drop(child.wait());
+                return None;
+            }
+        }
+    }
+    let output: std::process::Output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .find_map(parse_version)
+}
 
-    let config: Config = Config::default().unwrap();
-    let workspace: Workspace = Workspace::new(toml_path.as_ref(), &config).unwrap();
-    let mut compile_options: CompileOptions =
-        CompileOptions::new(&config, CompileMode::Build).unwrap();
-    compile_options.build_config.requested_profile = InternedString::new("release");
-    return cargo::ops::compile(&workspace, &compile_options).is_ok();
+pub fn rtop_requirement_matches(
+    requirement: &str,
+    rtop_version: &Version,
+) -> Result<bool, RtpmError> {
+    let version_requirement: VersionReq = VersionReq::parse(requirement).map_err(|error| {
+        RtpmError::InvalidInput(format!(
+            "The Rtop requirement {} is not a valid semver range ({}).",
+            requirement, error
+        ))
+    })?;
+    Ok(version_requirement.matches(rtop_version))
 }
 
-pub fn get_raw_url(url: &Url) -> Option<Url> {
-    let url_host: &str = url.host_str().unwrap();
+// Fails when the plugin requires another Rtop version, only warns when the Rtop version is unknown.
+pub fn verify_rtop_compatibility(
+    plugin_id: &str,
+    requirement: Option<&str>,
+    rtop_version: Option<&Version>,
+) -> Result<(), RtpmError> {
+    let requirement_range: &str = if let Some(requirement_range) = requirement {
+        requirement_range
+    } else {
+        return Ok(());
+    };
+    if let Some(version) = rtop_version {
+        if !rtop_requirement_matches(requirement_range, version)? {
+            return Err(RtpmError::IncompatibleRtop {
+                plugin: plugin_id.to_owned(),
+                requirement: requirement_range.to_owned(),
+                version: version.to_string(),
+            });
+        }
+    } else {
+        println!(
+            ":: {}",
+            format!(
+                "Unable to detect the Rtop version, the plugin {} requires Rtop {}.",
+                plugin_id, requirement_range
+            )
+            .yellow()
+        );
+    }
+    Ok(())
+}
+
+pub fn compare_versions(installed: &str, available: &str) -> VersionChange {
+    let (installed_version, available_version): (Version, Version) =
+        match (parse_version(installed), parse_version(available)) {
+            (Some(installed_version), Some(available_version)) => {
+                (installed_version, available_version)
+            }
+            _ if installed == available => return VersionChange::UpToDate,
+            _ => return VersionChange::Unknown,
+        };
+    if available_version < installed_version {
+        VersionChange::Downgrade
+    } else if available_version == installed_version {
+        VersionChange::UpToDate
+    } else if available_version.major != installed_version.major {
+        VersionChange::Major
+    } else if available_version.minor != installed_version.minor {
+        VersionChange::Minor
+    } else {
+        VersionChange::Patch
+    }
+}
+
+// Compares the manifest with the checksums pinned by the repository entry.
+// `signed_repository` is set when the repository has a pinned key, the signature of its index then
+// only covers the manifest through `manifest_sha256`.
+pub fn verify_repository_entry(
+    entry: &RepositoryPluginEntry,
+    plugin_manifest: &PluginManifest,
+    manifest_hash: &str,
+    signed_repository: Option<&str>,
+) -> Result<(), RtpmError> {
+    if let (Some(repository), None) = (signed_repository, &entry.manifest_sha256) {
+        return Err(RtpmError::Signature {
+            repository: repository.to_owned(),
+            message: format!(
+                "the entry of the plugin {} has no manifest_sha256 to authenticate its manifest",
+                entry.id
+            ),
+        });
+    }
+    if let Some(expected) = &entry.manifest_sha256 {
+        if !expected.eq_ignore_ascii_case(manifest_hash) {
+            return Err(RtpmError::ChecksumMismatch {
+                subject: format!("the manifest of the plugin {}", entry.id),
+                expected: expected.clone(),
+                actual: manifest_hash.to_owned(),
+            });
+        }
+    }
+    for (target, expected) in &entry.artifacts {
+        let actual: String = plugin_manifest.artifacts.get(target).map_or_else(
+            || "no artifact".to_owned(),
+            |artifact| artifact.sha256.clone(),
+        );
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(RtpmError::ChecksumMismatch {
+                subject: format!("the {} artifact of the plugin {}", target, entry.id),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Plugin ids name directories and files of rtpm, an id must be a single path component.
+pub fn is_plugin_id(id: &str) -> bool {
+    let mut components: Components = Path::new(id).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !id.contains(['/', '\\'])
+}
+
+// The network, credential and build settings are process-wide, loading different ones twice is an error.
+pub fn set_once<T: PartialEq>(
+    cell: &OnceLock<T>,
+    value: T,
+    settings: &str,
+) -> Result<(), RtpmError> {
+    if let Err(rejected) = cell.set(value) {
+        if cell.get() != Some(&rejected) {
+            return Err(RtpmError::InvalidInput(format!(
+                "The {} settings are already loaded from another configuration, they can only be loaded once per process.",
+                settings
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+pub fn current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// The shared target directory holds the libraries of every plugin, only the one named after the crate matches.
+pub fn find_shared_library(
+    directory: &Path,
+    library_name: &str,
+) -> Result<Option<PathBuf>, RtpmError> {
+    if !directory.exists() {
+        return Ok(None);
+    }
+    for entry in std::fs::read_dir(directory)? {
+        let path: PathBuf = entry?.path();
+        let stem: String = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        if is_shared_library(&path)
+            && (stem == library_name || stem == format!("lib{}", library_name))
+        {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+// Only known hosts have a raw URL, the manifest of other hosts is read from a shallow clone.
+pub fn get_raw_url(url: &Url, reference: &str) -> Option<Url> {
+    let url_host: &str = url.host_str()?;
     let url_path: &str = url.path();
     let url_split: Vec<&str> = url_path.split('/').filter(|&s| !s.is_empty()).collect();
+    if url_split.len() < 2 {
+        return None;
+    }
 
     match url_host {
-        "github.com" => Option::from(
-            Url::parse(&format!(
-                "https://raw.githubusercontent.com/{}/{}/main/",
-                url_split[0], url_split[1]
-            ))
-            .unwrap(),
-        ),
-        "gitlab.com" => Option::from(
-            Url::parse(&format!(
-                "https://gitlab.com/{}/{}/-/raw/main/",
-                url_split[0], url_split[1]
-            ))
-            .unwrap(),
-        ),
-        _ => {
-            println!(":: {}", "Currently, only GitHub and GitLab are supported for external plugins. You can open an issue on: https://github.com/RtopRS/RtopPluginManager/issues/new so I can add another site.".bold().red());
-            None
-        }
+        "github.com" => Url::parse(&format!(
+            "https://raw.githubusercontent.com/{}/{}/{}/",
+            url_split[0], url_split[1], reference
+        ))
+        .ok(),
+        "gitlab.com" => Url::parse(&format!(
+            "https://gitlab.com/{}/{}/-/raw/{}/",
+            url_split[0], url_split[1], reference
+        ))
+        .ok(),
+        _ => None,
     }
 }
+// A missing repository is only reported, the commands reading the configuration don't hold the lock to rewrite it.
 pub fn search_plugin(
     plugin_name: &str,
-    mut rtpm_config: RTPMConfig,
-    rtpm_config_path: &Path,
+    rtpm_config: &RTPMConfig,
+    paths: &Paths,
     print_if_found: bool,
-) -> Option<PathBuf> {
+) -> Result<Option<PathBuf>, RtpmError> {
     let mut repository_path_opt: Option<PathBuf> = None;
-    for repository in rtpm_config.repositories.clone() {
-        let path: PathBuf = dirs::data_dir()
-            .unwrap()
-            .join("rtop")
-            .join("repositories")
-            .join(repository.clone());
+    for repository in &rtpm_config.repositories {
+        let path: PathBuf = paths.repository(repository);
         if !path.exists() {
             println!(
                 ":: {}",
                 format!(
-                    "The repository {} is not or no longer present, I delete it.",
-                    repository
+                    "The repository {} is not or no longer present, add it again or remove it with rtpm -R {}.",
+                    repository, repository
                 )
                 .yellow()
             );
-            let index: usize = rtpm_config
-                .repositories
-                .iter()
-                .position(|r| r == &repository)
-                .unwrap();
-            rtpm_config.repositories.remove(index);
-            save_json_to_file(&rtpm_config, rtpm_config_path.to_path_buf());
             continue;
         }
-        let repository_plugins: RepositoryPlugin = read_json_file(&path.join("plugins.json"));
-        if repository_plugins.plugins.contains(&plugin_name.to_owned()) {
+        let repository_plugins: RepositoryPlugin = read_json_file(&path.join("plugins.json"))?;
+        if repository_plugins.entry(plugin_name).is_some() {
             if print_if_found {
                 println!(
                     ":: {}",
@@ -104,75 +535,76 @@ pub fn search_plugin(
             break;
         }
     }
-    repository_path_opt
+    Ok(repository_path_opt)
 }
 
-pub fn save_json_to_file<T>(json: &T, path: PathBuf)
+pub fn save_json_to_file<T>(json: &T, path: PathBuf) -> Result<(), RtpmError>
 where
     T: ?Sized + Serialize,
 {
-    std::fs::write(path.clone(), serde_json::to_string_pretty(&json).unwrap()).unwrap_or_else(
-        |e| {
-            println!(
-                ":: {}",
-                format!(
-                    "An error occurred while writing to the {} file ({}).",
-                    path.into_os_string().into_string().unwrap(),
-                    e
-                )
-                .bold()
-                .red()
-            );
-        },
-    );
+    let content: String =
+        serde_json::to_string_pretty(&json).map_err(|error| RtpmError::ManifestParse {
+            path: path.clone(),
+            error,
+        })?;
+    write_atomically(&path, content.as_bytes()).map_err(|error| RtpmError::ConfigIo { path, error })
 }
 
-pub fn read_json_file<T>(path: &PathBuf) -> T
-where
-    for<'a> T: serde::Deserialize<'a>,
-{
-    serde_json::from_str(&std::fs::read_to_string(path).unwrap_or_else(|_| "{}".to_owned()))
-        .unwrap()
-}
+// The content is written to a temporary file which then replaces the target, so a crash never leaves a truncated file.
+fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let file_name: String = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let temp_path: PathBuf = path.with_file_name(format!(".{}.tmp", file_name));
 
-pub fn verify_device_specification(plugin_manifest: &PluginManifest) -> bool {
-    if let Some(os) = &plugin_manifest.os {
-        return !(!os.is_empty() && !os.contains(&std::env::consts::OS.to_owned()));
+    let mut file: std::fs::File = std::fs::File::create(&temp_path)?;
+    if let Err(error) = file.write_all(content).and_then(|_| file.sync_all()) {
+        drop(std::fs::remove_file(&temp_path));
+        return Err(error);
     }
+    drop(file);
+    std::fs::rename(&temp_path, path)?;
 
-    if let Some(arch) = &plugin_manifest.arch {
-        return !(!arch.is_empty() && !arch.contains(&std::env::consts::ARCH.to_owned()));
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        drop(std::fs::File::open(parent).and_then(|directory| directory.sync_all()));
     }
+    Ok(())
+}
 
-    true
+pub fn read_json_file<T>(path: &PathBuf) -> Result<T, RtpmError>
+where
+    for<'a> T: serde::Deserialize<'a>,
+{
+    let content: String = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => "{}".to_owned(),
+        Err(error) => {
+            return Err(RtpmError::ConfigIo {
+                path: path.clone(),
+                error,
+            })
+        }
+    };
+    serde_json::from_str(&content).map_err(|error| RtpmError::ManifestParse {
+        path: path.clone(),
+        error,
+    })
 }
 
-pub fn contain_clap_arg(name: &str, matches: &clap::ArgMatches) -> bool {
-    matches
-        .get_one::<bool>(name)
-        .unwrap_or_else(|| {
-            println!(
-                "{}",
-                "A clap error occurred, please try again.".red().bold()
-            );
-            std::process::exit(22);
-        })
-        .to_owned()
+pub fn verify_device_specification(plugin_manifest: &PluginManifest) -> Result<(), String> {
+    verify_platform(plugin_manifest, &Host::current())
+}
+
+pub fn contain_clap_arg(name: &str, matches: &clap::ArgMatches) -> Result<bool, RtpmError> {
+    matches.get_one::<bool>(name).copied().ok_or_else(|| {
+        RtpmError::InvalidInput("A clap error occurred, please try again.".to_owned())
+    })
 }
 
-pub fn user_input_choice() -> bool {
+pub fn user_input_choice() -> Result<bool, RtpmError> {
     drop(std::io::stdout().flush());
     let mut user_response: String = String::new();
-    std::io::stdin()
-        .read_line(&mut user_response)
-        .unwrap_or_else(|_| {
-            println!(
-                "{}",
-                "An error occurred while reading the user input."
-                    .red()
-                    .bold()
-            );
-            std::process::exit(22);
-        });
-    vec!["y", "yes", "ok", "o"].contains(&user_response.trim().to_lowercase().as_str())
+    std::io::stdin().read_line(&mut user_response)?;
+    Ok(["y", "yes", "ok", "o"].contains(&user_response.trim().to_lowercase().as_str()))
 }
@@ -0,0 +1,9 @@
+use tempfile::TempDir;
+
+// Removed when dropped, even if the test panics.
+pub fn temp_dir(name: &str) -> TempDir {
+    tempfile::Builder::new()
+        .prefix(&format!("rtpm_test_{}_", name))
+        .tempdir()
+        .unwrap()
+}
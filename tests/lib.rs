@@ -1 +1,13 @@
+mod common;
+mod tests_artifact;
+mod tests_build;
+mod tests_credentials;
+mod tests_git;
+mod tests_lock;
+mod tests_network;
+mod tests_paths;
+mod tests_platform;
+mod tests_progress;
+mod tests_resolver;
+mod tests_signature;
 mod tests_utils;
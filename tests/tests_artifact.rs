@@ -0,0 +1,62 @@
+use crate::common::temp_dir;
+use rtpm::util::artifact::{fetch_artifact, select_artifact, ArtifactBase};
+use rtpm::util::error::RtpmError;
+use rtpm::util::structs::PluginManifest;
+use rtpm::util::utils::sha256_hex;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+#[test]
+fn test_fetch_artifact_from_repository() {
+    let temp: TempDir = temp_dir("fetch_artifact");
+    let root: &Path = temp.path();
+    let repository_path: PathBuf = root.join("repository");
+    std::fs::create_dir_all(repository_path.join("artifacts")).unwrap();
+    std::fs::write(
+        repository_path.join("artifacts").join("libcpu.so"),
+        b"library",
+    )
+    .unwrap();
+
+    let manifest: PluginManifest = serde_json::from_value(json!({
+        "id": "cpu",
+        "name": "CPU",
+        "description": "",
+        "version": "1.0.0",
+        "url": "https://github.com/RtopRS/cpu",
+        "artifacts": {
+            "cfg(any(unix, windows))": {
+                "path": "artifacts/libcpu.so",
+                "sha256": sha256_hex(b"library"),
+            },
+        },
+    }))
+    .unwrap();
+    let (target, artifact) = select_artifact(&manifest).unwrap();
+    assert_eq!("cfg(any(unix, windows))", target);
+
+    let base: ArtifactBase = ArtifactBase::Directory(repository_path.clone());
+    let destination: PathBuf =
+        fetch_artifact(artifact, Some(&base), &root.join("staging")).unwrap();
+    assert_eq!(root.join("staging").join("libcpu.so"), destination);
+    assert_eq!(b"library".to_vec(), std::fs::read(&destination).unwrap());
+
+    let tampered: PluginManifest = serde_json::from_value(json!({
+        "id": "cpu",
+        "name": "CPU",
+        "description": "",
+        "version": "1.0.0",
+        "url": "https://github.com/RtopRS/cpu",
+        "artifacts": {
+            "cfg(any(unix, windows))": { "path": "artifacts/libcpu.so", "sha256": sha256_hex(b"other") },
+        },
+    }))
+    .unwrap();
+    let (_, tampered_artifact) = select_artifact(&tampered).unwrap();
+    assert!(matches!(
+        fetch_artifact(tampered_artifact, Some(&base), &root.join("tampered")),
+        Err(RtpmError::ChecksumMismatch { .. })
+    ));
+    assert!(!root.join("tampered").exists());
+}
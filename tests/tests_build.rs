@@ -0,0 +1,75 @@
+use crate::common::temp_dir;
+use rtpm::util::build::{cdylib_from_messages, library_name};
+use rtpm::util::utils::find_shared_library;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+#[test]
+fn test_cdylib_from_messages() {
+    let messages: &str = r#"{"reason":"compiler-artifact","manifest_path":"/plugins/dep/Cargo.toml","target":{"kind":["lib"],"name":"dep"},"filenames":["/target/release/libdep.rlib"]}
+{"reason":"compiler-artifact","manifest_path":"/plugins/other/Cargo.toml","target":{"kind":["cdylib"],"name":"other"},"filenames":["/target/release/libother.so"]}
+{"reason":"compiler-artifact","manifest_path":"/plugins/example/Cargo.toml","target":{"kind":["lib","cdylib"],"name":"example"},"filenames":["/target/release/libexample.rlib","/target/release/libexample.so"]}
+{"reason":"build-finished","success":true}"#;
+    assert_eq!(
+        Some(PathBuf::from("/target/release/libexample.so")),
+        cdylib_from_messages(messages, Path::new("/plugins/example/Cargo.toml"))
+    );
+    assert_eq!(
+        Some(PathBuf::from("/target/release/libother.so")),
+        cdylib_from_messages(messages, Path::new("/plugins/other/Cargo.toml"))
+    );
+    // The cdylib of another package is never installed under the id of the plugin.
+    assert_eq!(
+        None,
+        cdylib_from_messages(messages, Path::new("/plugins/missing/Cargo.toml"))
+    );
+    assert_eq!(
+        None,
+        cdylib_from_messages(
+            r#"{"reason":"build-finished","success":false}"#,
+            Path::new("/plugins/example/Cargo.toml")
+        )
+    );
+}
+
+#[test]
+fn test_find_shared_library() {
+    let temp: TempDir = temp_dir("find_shared_library");
+    let directory: &Path = temp.path();
+    std::fs::create_dir_all(directory.join("release")).unwrap();
+    std::fs::write(
+        directory.join("Cargo.toml"),
+        "[package]\nname = \"rtop-clock\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    for file in [
+        "libother_plugin.so",
+        "librtop_clock.dylib",
+        "librtop_clock.d",
+    ] {
+        std::fs::write(directory.join("release").join(file), b"").unwrap();
+    }
+
+    let name: String = library_name(&directory.join("Cargo.toml"))
+        .unwrap()
+        .unwrap();
+    assert_eq!("rtop_clock", name);
+    assert_eq!(
+        Some(directory.join("release").join("librtop_clock.dylib")),
+        find_shared_library(&directory.join("release"), &name).unwrap()
+    );
+    assert_eq!(
+        None,
+        find_shared_library(&directory.join("release"), "missing").unwrap()
+    );
+
+    std::fs::write(
+        directory.join("Cargo.toml"),
+        "[package]\nname = \"rtop-clock\"\n\n[lib]\nname = \"clock\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        Some("clock".to_owned()),
+        library_name(&directory.join("Cargo.toml")).unwrap()
+    );
+}
@@ -0,0 +1,86 @@
+use rtpm::git::credentials::{
+    git_command_credentials, host_credentials, url_host, CredentialsFile, HostCredentials,
+};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_url_host() {
+    assert_eq!(
+        Some("git.example.com".to_owned()),
+        url_host("https://git.example.com/rtop/plugin.git")
+    );
+    assert_eq!(
+        Some("codeberg.org".to_owned()),
+        url_host("ssh://git@codeberg.org/rtop/plugin.git")
+    );
+    assert_eq!(
+        Some("github.com".to_owned()),
+        url_host("git@github.com:RtopRS/Plugin")
+    );
+    assert_eq!(None, url_host("/home/rtop/plugin"));
+}
+
+#[test]
+fn test_host_credentials() {
+    let credentials: CredentialsFile = serde_json::from_str(
+        r#"{"hosts": {"gitea.rtpm-test.local": {"username": "rtop", "token": "file-token"}}}"#,
+    )
+    .unwrap();
+    let from_file: HostCredentials = host_credentials(&credentials, "gitea.rtpm-test.local");
+    assert_eq!(Some("rtop"), from_file.username.as_deref());
+    assert_eq!(Some("file-token"), from_file.token.as_deref());
+
+    std::env::set_var("RTPM_GIT_TOKEN_GITEA_RTPM_TEST_LOCAL", "env-token");
+    let from_env: HostCredentials = host_credentials(&credentials, "gitea.rtpm-test.local");
+    std::env::remove_var("RTPM_GIT_TOKEN_GITEA_RTPM_TEST_LOCAL");
+    assert_eq!(Some("env-token"), from_env.token.as_deref());
+
+    let configured: CredentialsFile =
+        serde_json::from_str(r#"{"hosts": {"forge.rtpm-test.local": {"username": "rtop"}}}"#)
+            .unwrap();
+    std::env::set_var("RTPM_GIT_TOKEN", "global-token");
+    let configured_host: HostCredentials = host_credentials(&configured, "forge.rtpm-test.local");
+    let other_host: HostCredentials = host_credentials(&configured, "evil.rtpm-test.local");
+    std::env::remove_var("RTPM_GIT_TOKEN");
+    assert_eq!(Some("global-token"), configured_host.token.as_deref());
+    assert_eq!(None, other_host.token);
+}
+
+#[test]
+fn test_git_command_credentials() {
+    let mut command: Command = Command::new("git");
+    git_command_credentials(
+        &mut command,
+        &HostCredentials {
+            username: Some("rtop".to_owned()),
+            token: Some("secret-token".to_owned()),
+            ssh_key: Some(PathBuf::from("/home/rtop/.ssh/it's_key")),
+            ssh_passphrase: None,
+        },
+    );
+    let arguments: Vec<&OsStr> = command.get_args().collect();
+    assert!(arguments
+        .iter()
+        .all(|argument| !argument.to_string_lossy().contains("secret-token")));
+    assert!(arguments
+        .iter()
+        .any(|argument| argument.to_string_lossy().contains("RTPM_GIT_PASSWORD")));
+    let environment: Vec<(&OsStr, Option<&OsStr>)> = command.get_envs().collect();
+    assert!(environment.contains(&(
+        OsStr::new("RTPM_GIT_PASSWORD"),
+        Some(OsStr::new("secret-token"))
+    )));
+    assert!(environment.contains(&(
+        OsStr::new("GIT_SSH_COMMAND"),
+        Some(OsStr::new(
+            "ssh -o BatchMode=yes -o IdentitiesOnly=yes -i '/home/rtop/.ssh/it'\\''s_key' -l 'rtop'"
+        ))
+    )));
+
+    let mut anonymous: Command = Command::new("git");
+    git_command_credentials(&mut anonymous, &HostCredentials::default());
+    assert_eq!(0, anonymous.get_args().count());
+    assert_eq!(0, anonymous.get_envs().count());
+}
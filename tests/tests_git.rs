@@ -0,0 +1,206 @@
+use crate::common::temp_dir;
+use git2::{Oid, Repository, Signature};
+use rtpm::git::{clone, pull, revision, source_cache};
+use rtpm::util::error::RtpmError;
+use rtpm::util::structs::PluginPin;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn commit_file(repo: &Repository, path: &Path, content: &str) -> Oid {
+    std::fs::write(path.join("file.txt"), content).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("file.txt")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature: Signature = Signature::now("rtpm", "rtpm@example.com").unwrap();
+    let parents = repo
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_commit().ok())
+        .into_iter()
+        .collect::<Vec<_>>();
+    let parents_refs = parents.iter().collect::<Vec<_>>();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        content,
+        &tree,
+        &parents_refs,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_checkout_commit() {
+    let temp: TempDir = temp_dir("checkout_commit");
+    let path: &Path = temp.path();
+    let repo: Repository = Repository::init(path).unwrap();
+    let first: Oid = commit_file(&repo, path, "first");
+    let second: Oid = commit_file(&repo, path, "second");
+    assert_eq!(second.to_string(), revision::head_commit(path).unwrap());
+
+    revision::checkout_commit(path, &first.to_string()).unwrap();
+    assert_eq!(first.to_string(), revision::head_commit(path).unwrap());
+    assert_eq!(
+        "first",
+        std::fs::read_to_string(path.join("file.txt")).unwrap()
+    );
+}
+
+#[test]
+fn test_checkout_pin_tag() {
+    let temp: TempDir = temp_dir("checkout_pin_tag");
+    let path: &Path = temp.path();
+    let repo: Repository = Repository::init(path).unwrap();
+    let first: Oid = commit_file(&repo, path, "first");
+    repo.tag_lightweight("v1.0.0", &repo.find_object(first, None).unwrap(), false)
+        .unwrap();
+    commit_file(&repo, path, "second");
+
+    revision::checkout_pin(path, &PluginPin::Tag("v1.0.0".to_owned())).unwrap();
+    assert_eq!(first.to_string(), revision::head_commit(path).unwrap());
+}
+
+#[test]
+fn test_default_branch() {
+    let temp: TempDir = temp_dir("default_branch");
+    let path: &Path = temp.path();
+    let origin_path: PathBuf = path.join("origin");
+    let clone_path: PathBuf = path.join("clone");
+    let repo: Repository = Repository::init(&origin_path).unwrap();
+    repo.set_head("refs/heads/trunk").unwrap();
+    commit_file(&repo, &origin_path, "first");
+
+    Repository::clone(origin_path.to_str().unwrap(), &clone_path).unwrap();
+    assert_eq!("trunk", revision::default_branch(&clone_path).unwrap());
+}
+
+#[test]
+fn test_checkout_expected_commit() {
+    let temp: TempDir = temp_dir("checkout_expected_commit");
+    let path: &Path = temp.path();
+    let repo: Repository = Repository::init(path).unwrap();
+    let first: Oid = commit_file(&repo, path, "first");
+    commit_file(&repo, path, "second");
+
+    assert_eq!(
+        first.to_string(),
+        revision::checkout_expected_commit(path, "cpu", &first.to_string()).unwrap()
+    );
+    assert!(matches!(
+        revision::verify_commit(path, "cpu", &first.to_string()[..10]),
+        Err(RtpmError::ChecksumMismatch { .. })
+    ));
+    assert!(matches!(
+        revision::checkout_expected_commit(path, "cpu", "0123456789abcdef"),
+        Err(RtpmError::ChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_fetch_from_mirrors() {
+    let temp: TempDir = temp_dir("fetch_from_mirrors");
+    let root: &Path = temp.path();
+    let mirror_path: PathBuf = root.join("mirror");
+    let mirror: Repository = Repository::init(&mirror_path).unwrap();
+    commit_file(&mirror, &mirror_path, "first");
+    let clone_path: PathBuf = root.join("clone");
+    let urls: Vec<String> = vec![
+        root.join("unreachable").to_string_lossy().into_owned(),
+        mirror_path.to_string_lossy().into_owned(),
+    ];
+    assert_eq!(
+        urls[1],
+        clone::clone_from_mirrors(&urls, &clone_path).unwrap()
+    );
+
+    let second: Oid = commit_file(&mirror, &mirror_path, "second");
+    let repo: Repository = Repository::open(&clone_path).unwrap();
+    repo.remote_set_url("origin", &urls[0]).unwrap();
+    let (fetch_commit, used) = pull::fetch_from_mirrors(&repo, &["HEAD"], &urls).unwrap();
+    assert_eq!(second, fetch_commit.id());
+    assert_eq!(urls[1], used);
+    assert_eq!(
+        Some(urls[1].as_str()),
+        repo.find_remote("origin").unwrap().url()
+    );
+}
+
+#[test]
+fn test_shallow_clone() {
+    let temp: TempDir = temp_dir("shallow_clone");
+    let root: &Path = temp.path();
+    let origin_path: PathBuf = root.join("origin");
+    let origin: Repository = Repository::init(&origin_path).unwrap();
+    let first: Oid = commit_file(&origin, &origin_path, "first");
+    commit_file(&origin, &origin_path, "second");
+    let url: String = format!("file://{}", origin_path.display());
+
+    clone::shallow_clone(&url, None, &root.join("head")).unwrap();
+    assert_eq!(
+        "second",
+        std::fs::read_to_string(root.join("head").join("file.txt")).unwrap()
+    );
+    clone::shallow_clone(&url, Some(&first.to_string()), &root.join("commit")).unwrap();
+    assert_eq!(
+        "first",
+        std::fs::read_to_string(root.join("commit").join("file.txt")).unwrap()
+    );
+}
+
+#[test]
+fn test_source_cache() {
+    let temp: TempDir = temp_dir("source_cache");
+    let root: &Path = temp.path();
+    let source_path: PathBuf = root.join("source");
+    let source: Repository = Repository::init(&source_path).unwrap();
+    let commit: Oid = commit_file(&source, &source_path, "cached");
+    let cache_path: PathBuf = root.join("cache").join("plugin");
+    source_cache::store_source_cache(&source_path, &cache_path).unwrap();
+    std::fs::remove_dir_all(&source_path).unwrap();
+
+    let restored_path: PathBuf = root.join("restored");
+    source_cache::restore_source_cache(&cache_path, &restored_path).unwrap();
+    assert_eq!(
+        commit.to_string(),
+        revision::head_commit(&restored_path).unwrap()
+    );
+    assert_eq!(
+        "cached",
+        std::fs::read_to_string(restored_path.join("file.txt")).unwrap()
+    );
+}
+
+#[test]
+fn test_shallow_clone_deepen_and_fetch() {
+    let temp: TempDir = temp_dir("shallow_deepen");
+    let root: &Path = temp.path();
+    let origin_path: PathBuf = root.join("origin");
+    let origin: Repository = Repository::init(&origin_path).unwrap();
+    let first: Oid = commit_file(&origin, &origin_path, "first");
+    commit_file(&origin, &origin_path, "second");
+    let url: String = format!("file://{}", origin_path.display());
+    let clone_path: PathBuf = root.join("clone");
+    clone::clone(&url, &clone_path).unwrap();
+    let repo: Repository = Repository::open(&clone_path).unwrap();
+    assert!(repo.is_shallow());
+
+    revision::checkout_commit(&clone_path, &first.to_string()).unwrap();
+    assert_eq!(
+        first.to_string(),
+        revision::head_commit(&clone_path).unwrap()
+    );
+
+    let third: Oid = commit_file(&origin, &origin_path, "third");
+    let branch: String = origin.head().unwrap().shorthand().unwrap().to_owned();
+    let mut remote = repo.find_remote("origin").unwrap();
+    let (fetch_commit, updated) = pull::do_fetch(&repo, &[branch.as_str()], &mut remote).unwrap();
+    assert!(updated);
+    assert_eq!(third, fetch_commit.id());
+    pull::do_merge(&repo, &branch, &fetch_commit).unwrap();
+    assert_eq!(
+        "third",
+        std::fs::read_to_string(clone_path.join("file.txt")).unwrap()
+    );
+}
@@ -0,0 +1,21 @@
+use crate::common::temp_dir;
+use rtpm::util::error::RtpmError;
+use rtpm::util::lock::RtpmLock;
+use rtpm::util::paths::Paths;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[test]
+fn test_lock_is_exclusive() {
+    let temp: TempDir = temp_dir("lock_is_exclusive");
+    let root: &Path = temp.path();
+    let paths: Paths = Paths::resolve(Some(root)).unwrap();
+
+    let lock: RtpmLock = RtpmLock::acquire(&paths, false).unwrap();
+    assert!(matches!(
+        RtpmLock::acquire(&paths, false),
+        Err(RtpmError::Locked(_))
+    ));
+    drop(lock);
+    assert!(RtpmLock::acquire(&paths, false).is_ok());
+}
@@ -0,0 +1,61 @@
+use crate::common::temp_dir;
+use rtpm::util::network::write_ca_bundle;
+use rtpm::util::structs::NetworkConfig;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+#[test]
+fn test_proxy_for() {
+    let network_config: NetworkConfig = NetworkConfig {
+        proxy: Some("http://proxy.corp:3128".to_owned()),
+        no_proxy: Some("localhost, .corp.internal".to_owned()),
+        ca_certificates: Vec::new(),
+        offline: false,
+        clone_depth: None,
+    };
+    assert_eq!(
+        Some("http://proxy.corp:3128"),
+        network_config.proxy_for("https://github.com/RtopRS/PluginsRepository")
+    );
+    assert_eq!(
+        Some("http://proxy.corp:3128"),
+        network_config.proxy_for("git@github.com:RtopRS/Plugin")
+    );
+    assert_eq!(
+        None,
+        network_config.proxy_for("https://gitea.corp.internal/rtop/plugin")
+    );
+    assert_eq!(
+        None,
+        network_config.proxy_for("http://localhost:3000/plugin")
+    );
+    assert_eq!(
+        None,
+        NetworkConfig::default().proxy_for("https://github.com/RtopRS/Plugin")
+    );
+}
+
+#[test]
+fn test_write_ca_bundle() {
+    let temp: TempDir = temp_dir("ca_bundle");
+    let directory: &Path = temp.path();
+    let certificate_path: PathBuf = directory.join("corporate.pem");
+    std::fs::write(
+        &certificate_path,
+        "-----BEGIN CERTIFICATE-----\nCORPORATE\n-----END CERTIFICATE-----\n",
+    )
+    .unwrap();
+
+    let bundle_path: PathBuf = write_ca_bundle(
+        &[certificate_path],
+        &directory.join("cache").join("ca-bundle.pem"),
+    )
+    .unwrap();
+    let bundle: String = std::fs::read_to_string(bundle_path).unwrap();
+    assert!(bundle.starts_with("-----BEGIN CERTIFICATE-----\nCORPORATE\n"));
+    assert!(write_ca_bundle(
+        &[directory.join("missing.pem")],
+        &directory.join("other.pem")
+    )
+    .is_err());
+}
@@ -0,0 +1,30 @@
+use crate::common::temp_dir;
+use rtpm::util::paths::Paths;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+#[test]
+fn test_paths_resolve_root() {
+    let temp: TempDir = temp_dir("root");
+    let root: &Path = temp.path();
+    let paths: Paths = Paths::resolve(Some(root)).unwrap();
+    assert_eq!(root.join("plugins"), paths.plugins());
+    assert_eq!(
+        root.join("repositories").join("rtop"),
+        paths.repository("rtop")
+    );
+    assert_eq!(root.join("rtpm.json"), paths.rtpm_config());
+    assert_eq!(root.join("config.json"), paths.rtop_config());
+}
+
+#[test]
+fn test_paths_new() {
+    let paths: Paths = Paths::new(PathBuf::from("/data"), PathBuf::from("/config"));
+    assert_eq!(Path::new("/data/plugins/foo"), paths.plugin("foo"));
+    assert_eq!(Path::new("/config/rtpm.json"), paths.rtpm_config());
+    assert_eq!(Path::new("/data/cache/target"), paths.build_cache());
+    assert_eq!(
+        Path::new("/data/cache/sources/foo"),
+        paths.source_cache("foo")
+    );
+}
@@ -0,0 +1,75 @@
+use rtpm::util::platform::{matches_platform, verify_platform, Host};
+use rtpm::util::structs::PluginManifest;
+use serde_json::json;
+
+fn linux_aarch64_musl() -> Host {
+    Host {
+        os: "linux".to_owned(),
+        arch: "aarch64".to_owned(),
+        family: "unix".to_owned(),
+        env: "musl".to_owned(),
+        pointer_width: "64".to_owned(),
+    }
+}
+
+fn manifest(constraints: serde_json::Value) -> PluginManifest {
+    let mut manifest: serde_json::Value = json!({
+        "id": "cpu",
+        "name": "CPU",
+        "description": "",
+        "version": "1.0.0",
+        "url": "https://github.com/RtopRS/cpu",
+    });
+    if let (Some(fields), Some(extra_fields)) = (manifest.as_object_mut(), constraints.as_object())
+    {
+        fields.extend(extra_fields.clone());
+    }
+    serde_json::from_value(manifest).unwrap()
+}
+
+#[test]
+fn test_matches_platform() {
+    let host: Host = linux_aarch64_musl();
+    assert!(matches_platform("aarch64-unknown-linux-musl", &host).is_ok());
+    assert!(matches_platform("aarch64-unknown-linux", &host).is_ok());
+    assert!(matches_platform("aarch64-unknown-linux-gnu", &host).is_err());
+    assert!(matches_platform("x86_64-unknown-linux-musl", &host).is_err());
+    assert!(matches_platform("aarch64-apple-darwin", &host).is_err());
+    assert!(matches_platform(
+        r#"cfg(all(target_os = "linux", target_arch = "aarch64"))"#,
+        &host
+    )
+    .is_ok());
+    assert!(matches_platform(r#"cfg(any(windows, target_env = "gnu"))"#, &host).is_err());
+    assert!(matches_platform(r#"cfg(not(target_env = "gnu"))"#, &host).is_ok());
+    assert!(matches_platform("cfg(all(unix", &host).is_err());
+    assert!(matches_platform("not-a-triple", &host).is_err());
+}
+
+#[test]
+fn test_verify_platform() {
+    let host: Host = linux_aarch64_musl();
+    assert!(verify_platform(&manifest(json!({})), &host).is_ok());
+    assert!(verify_platform(
+        &manifest(json!({ "os": ["linux"], "arch": ["aarch64"] })),
+        &host
+    )
+    .is_ok());
+    let reason: String = verify_platform(
+        &manifest(json!({ "os": ["linux"], "arch": ["x86_64"] })),
+        &host,
+    )
+    .unwrap_err();
+    assert!(reason.contains("architecture aarch64"));
+    assert!(verify_platform(
+        &manifest(json!({ "platforms": ["x86_64-pc-windows-msvc", "aarch64-unknown-linux-musl"] })),
+        &host
+    )
+    .is_ok());
+    let reason: String = verify_platform(
+        &manifest(json!({ "platforms": ["aarch64-unknown-linux-gnu"] })),
+        &host,
+    )
+    .unwrap_err();
+    assert!(reason.contains("the environment gnu does not match musl"));
+}
@@ -0,0 +1,35 @@
+use rtpm::util::error::RtpmError;
+use rtpm::util::progress::{notice, run_parallel};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[test]
+fn test_run_parallel() {
+    let items: Vec<(String, u64)> = (0..8).map(|i| (format!("item {}", i), i)).collect();
+    let running: AtomicUsize = AtomicUsize::new(0);
+    let max_running: AtomicUsize = AtomicUsize::new(0);
+    let results: Vec<Result<u64, RtpmError>> = run_parallel(&items, 3, |item: &u64| {
+        let current: usize = running.fetch_add(1, Ordering::SeqCst) + 1;
+        max_running.fetch_max(current, Ordering::SeqCst);
+        notice(&format!("Processing {}...", item));
+        std::thread::sleep(Duration::from_millis(20));
+        running.fetch_sub(1, Ordering::SeqCst);
+        if *item == 5 {
+            return Err(RtpmError::NotFound("missing".to_owned()));
+        }
+        Ok(item * 2)
+    });
+
+    assert!(max_running.load(Ordering::SeqCst) <= 3);
+    assert_eq!(8, results.len());
+    for (index, result) in results.iter().enumerate() {
+        match result {
+            Ok(value) => assert_eq!(index as u64 * 2, *value),
+            Err(error) => {
+                assert_eq!(5, index);
+                assert!(matches!(error, RtpmError::NotFound(_)));
+            }
+        }
+    }
+    assert!(run_parallel(&Vec::<(String, u64)>::new(), 3, |item: &u64| Ok(*item)).is_empty());
+}
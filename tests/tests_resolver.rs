@@ -0,0 +1,144 @@
+use crate::common::temp_dir;
+use rtpm::util::error::RtpmError;
+use rtpm::util::paths::Paths;
+use rtpm::util::resolver::{resolve_dependencies, Requirement, ResolvedPlugin};
+use rtpm::util::structs::RTPMConfig;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn write_repository(root: &Path, repository: &str, manifests: &[serde_json::Value]) {
+    let repository_path: PathBuf = root.join("repositories").join(repository);
+    std::fs::create_dir_all(repository_path.join("plugins")).unwrap();
+    let ids: Vec<&str> = manifests
+        .iter()
+        .map(|manifest| manifest["id"].as_str().unwrap())
+        .collect();
+    std::fs::write(
+        repository_path.join("plugins.json"),
+        json!({ "plugins": ids }).to_string(),
+    )
+    .unwrap();
+    for manifest in manifests {
+        std::fs::write(
+            repository_path
+                .join("plugins")
+                .join(format!("{}.json", manifest["id"].as_str().unwrap())),
+            manifest.to_string(),
+        )
+        .unwrap();
+    }
+}
+
+fn manifest(id: &str, version: &str, extra: serde_json::Value) -> serde_json::Value {
+    let mut manifest: serde_json::Value = json!({
+        "id": id,
+        "name": id,
+        "description": "",
+        "version": version,
+        "url": format!("https://github.com/RtopRS/{}", id),
+    });
+    if let (Some(fields), Some(extra_fields)) = (manifest.as_object_mut(), extra.as_object()) {
+        fields.extend(extra_fields.clone());
+    }
+    manifest
+}
+
+fn requirement(id: &str) -> Requirement {
+    Requirement {
+        id: id.to_owned(),
+        range: "*".to_owned(),
+        required_by: None,
+    }
+}
+
+#[test]
+fn test_resolve_dependencies() {
+    let temp: TempDir = temp_dir("resolve_dependencies");
+    let root: &Path = temp.path();
+    write_repository(
+        root,
+        "first",
+        &[
+            manifest(
+                "dashboard",
+                "1.0.0",
+                json!({ "dependencies": { "widgets": "^2.0" } }),
+            ),
+            manifest("widgets", "1.4.0", json!({})),
+        ],
+    );
+    write_repository(root, "second", &[manifest("widgets", "2.1.0", json!({}))]);
+    let rtpm_config: RTPMConfig = RTPMConfig {
+        repositories: vec!["first".to_owned(), "second".to_owned()],
+        ..RTPMConfig::default()
+    };
+    let paths: Paths = Paths::new(root.to_path_buf(), root.to_path_buf());
+
+    let resolved: Vec<ResolvedPlugin> =
+        resolve_dependencies(&[requirement("dashboard")], &rtpm_config, &paths).unwrap();
+    let order: Vec<(&str, &str, &str)> = resolved
+        .iter()
+        .map(|plugin| {
+            (
+                plugin.id.as_str(),
+                plugin.version.as_str(),
+                plugin.repository.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ("widgets", "2.1.0", "second"),
+            ("dashboard", "1.0.0", "first")
+        ],
+        order
+    );
+}
+
+#[test]
+fn test_resolve_dependencies_conflict() {
+    let temp: TempDir = temp_dir("resolve_dependencies_conflict");
+    let root: &Path = temp.path();
+    write_repository(
+        root,
+        "first",
+        &[
+            manifest("cpu", "1.0.0", json!({ "conflicts": ["cpu-legacy"] })),
+            manifest("cpu-legacy", "0.3.0", json!({})),
+        ],
+    );
+    let rtpm_config: RTPMConfig = RTPMConfig {
+        repositories: vec!["first".to_owned()],
+        ..RTPMConfig::default()
+    };
+    let paths: Paths = Paths::new(root.to_path_buf(), root.to_path_buf());
+
+    let result: Result<Vec<ResolvedPlugin>, RtpmError> = resolve_dependencies(
+        &[requirement("cpu"), requirement("cpu-legacy")],
+        &rtpm_config,
+        &paths,
+    );
+    assert!(matches!(result, Err(RtpmError::Dependency(_))));
+}
+
+#[test]
+fn test_resolve_dependencies_checksum() {
+    let temp: TempDir = temp_dir("resolve_dependencies_checksum");
+    let root: &Path = temp.path();
+    write_repository(root, "first", &[manifest("cpu", "1.0.0", json!({}))]);
+    std::fs::write(
+        root.join("repositories").join("first").join("plugins.json"),
+        json!({ "plugins": [{ "id": "cpu", "manifest_sha256": "0000" }] }).to_string(),
+    )
+    .unwrap();
+    let rtpm_config: RTPMConfig = RTPMConfig {
+        repositories: vec!["first".to_owned()],
+        ..RTPMConfig::default()
+    };
+    let paths: Paths = Paths::new(root.to_path_buf(), root.to_path_buf());
+
+    let result: Result<Vec<ResolvedPlugin>, RtpmError> =
+        resolve_dependencies(&[requirement("cpu")], &rtpm_config, &paths);
+    assert!(matches!(result, Err(RtpmError::ChecksumMismatch { .. })));
+}
@@ -0,0 +1,97 @@
+use rtpm::util::error::RtpmError;
+use rtpm::util::signature::{check_repository_signature, signed_repository, verify_index};
+use rtpm::util::structs::{
+    PluginManifest, RTPMConfig, RTPMConfigRepositoryElement, RepositoryPlugin,
+    RepositoryPluginEntry,
+};
+use rtpm::util::utils::verify_repository_entry;
+
+const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+#[test]
+fn test_verify_index() {
+    assert!(verify_index("test", b"test", SIGNATURE, PUBLIC_KEY).is_ok());
+    assert!(matches!(
+        verify_index("test", b"tampered", SIGNATURE, PUBLIC_KEY),
+        Err(RtpmError::Signature { .. })
+    ));
+}
+
+#[test]
+fn test_check_repository_signature_pins_key() {
+    let mut details: RTPMConfigRepositoryElement = RTPMConfigRepositoryElement::default();
+    check_repository_signature(
+        "test",
+        Some(PUBLIC_KEY),
+        b"test",
+        Some(SIGNATURE),
+        &mut details,
+    )
+    .unwrap();
+    assert_eq!(details.public_key.as_deref(), Some(PUBLIC_KEY));
+
+    // The pinned key is still required when the repository stops declaring it.
+    assert!(matches!(
+        check_repository_signature("test", None, b"test", None, &mut details),
+        Err(RtpmError::Signature { .. })
+    ));
+    assert!(matches!(
+        check_repository_signature(
+            "test",
+            Some("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO4"),
+            b"test",
+            Some(SIGNATURE),
+            &mut details,
+        ),
+        Err(RtpmError::Signature { .. })
+    ));
+}
+
+#[test]
+fn test_check_unsigned_repository() {
+    let mut details: RTPMConfigRepositoryElement = RTPMConfigRepositoryElement::default();
+    assert!(check_repository_signature("test", None, b"test", None, &mut details).is_ok());
+    assert!(details.public_key.is_none());
+}
+
+#[test]
+fn test_signed_repository_requires_manifest_checksums() {
+    let mut rtpm_config: RTPMConfig = RTPMConfig::default();
+    assert_eq!(None, signed_repository(&rtpm_config, "test"));
+    rtpm_config.repository_details.insert(
+        "test".to_owned(),
+        RTPMConfigRepositoryElement {
+            public_key: Some(PUBLIC_KEY.to_owned()),
+            ..RTPMConfigRepositoryElement::default()
+        },
+    );
+    assert_eq!(Some("test"), signed_repository(&rtpm_config, "test"));
+
+    let index: RepositoryPlugin = serde_json::from_str(
+        r#"{"plugins": ["unpinned", {"id": "pinned", "manifest_sha256": "ABC123"}]}"#,
+    )
+    .unwrap();
+    let manifest: PluginManifest = serde_json::from_value(serde_json::json!({
+        "id": "pinned", "name": "pinned", "description": "", "version": "1.0.0",
+        "url": "https://example.com/pinned", "author": null, "authors": null,
+        "license": null, "os": null, "arch": null
+    }))
+    .unwrap();
+    let unpinned: &RepositoryPluginEntry = index.entry("unpinned").unwrap();
+    let pinned: &RepositoryPluginEntry = index.entry("pinned").unwrap();
+    assert!(verify_repository_entry(unpinned, &manifest, "abc123", None).is_ok());
+    assert!(matches!(
+        verify_repository_entry(unpinned, &manifest, "abc123", Some("test")),
+        Err(RtpmError::Signature { .. })
+    ));
+    assert!(verify_repository_entry(pinned, &manifest, "abc123", Some("test")).is_ok());
+    assert!(matches!(
+        verify_repository_entry(pinned, &manifest, "def456", Some("test")),
+        Err(RtpmError::ChecksumMismatch { .. })
+    ));
+}
@@ -1,4 +1,13 @@
+use crate::common::temp_dir;
+use rtpm::util::error::RtpmError;
+use rtpm::util::structs::{
+    BuildOptions, PluginManifest, PluginPin, RTPMConfig, RTPMConfigPluginElement,
+    RepositoryManifest, RepositoryPlugin, VersionChange,
+};
 use rtpm::util::utils;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tempfile::TempDir;
 use url::Url;
 
 #[test]
@@ -12,18 +21,321 @@ fn test_convert_to_readable_unity() {
 fn test_get_raw_url() {
     assert_eq!(
         "https://raw.githubusercontent.com/RtopRS/PluginTemplate/main/",
-        utils::get_raw_url(&Url::parse("https://github.com/RtopRS/PluginTemplate/").unwrap())
-            .unwrap()
-            .as_str()
+        utils::get_raw_url(
+            &Url::parse("https://github.com/RtopRS/PluginTemplate/").unwrap(),
+            "main"
+        )
+        .unwrap()
+        .as_str()
     );
     assert_eq!(
         "https://gitlab.com/rtoprs/RtopPluginManager/-/raw/main/",
-        utils::get_raw_url(&Url::parse("https://gitlab.com/rtoprs/RtopPluginManager").unwrap())
-            .unwrap()
-            .as_str()
+        utils::get_raw_url(
+            &Url::parse("https://gitlab.com/rtoprs/RtopPluginManager").unwrap(),
+            "main"
+        )
+        .unwrap()
+        .as_str()
     );
     assert_eq!(
         None,
-        utils::get_raw_url(&Url::parse("https://sourceforge.net/projects/android-x86/").unwrap())
+        utils::get_raw_url(
+            &Url::parse("https://sourceforge.net/projects/android-x86/").unwrap(),
+            "main"
+        )
     );
 }
+
+#[test]
+fn test_read_json_file_malformed() {
+    let temp: TempDir = temp_dir("read_json_file_malformed");
+    let path: PathBuf = temp.path().join("rtpm.json");
+    std::fs::write(&path, "{ not json").unwrap();
+    let result: Result<RTPMConfig, RtpmError> = utils::read_json_file(&path);
+    assert!(matches!(result, Err(RtpmError::ManifestParse { .. })));
+}
+
+#[test]
+fn test_save_json_to_file_atomic() {
+    let temp: TempDir = temp_dir("save_json_to_file_atomic");
+    let directory: &Path = temp.path();
+    let path: PathBuf = directory.join("rtpm.json");
+    let config: RTPMConfig = RTPMConfig {
+        repositories: vec!["rtop".to_owned()],
+        ..RTPMConfig::default()
+    };
+    utils::save_json_to_file(&config, path.clone()).unwrap();
+    let saved: RTPMConfig = utils::read_json_file(&path).unwrap();
+    assert_eq!(vec!["rtop".to_owned()], saved.repositories);
+    assert!(!directory.join(".rtpm.json.tmp").exists());
+}
+
+#[test]
+fn test_sha256_hex() {
+    assert_eq!(
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        utils::sha256_hex(b"")
+    );
+}
+
+#[test]
+fn test_parse_plugin_pin() {
+    assert_eq!(
+        ("foo".to_owned(), None),
+        utils::parse_plugin_pin("foo").unwrap()
+    );
+    assert_eq!(
+        ("foo".to_owned(), Some(PluginPin::Tag("v1.2.0".to_owned()))),
+        utils::parse_plugin_pin("foo@v1.2.0").unwrap()
+    );
+    assert_eq!(
+        (
+            "foo".to_owned(),
+            Some(PluginPin::Branch("develop".to_owned()))
+        ),
+        utils::parse_plugin_pin("foo#develop").unwrap()
+    );
+    assert_eq!(
+        (
+            "foo".to_owned(),
+            Some(PluginPin::Commit("3e1f0a9".to_owned()))
+        ),
+        utils::parse_plugin_pin("foo@3e1f0a9").unwrap()
+    );
+    assert_eq!(
+        ("git@github.com:RtopRS/Plugin".to_owned(), None),
+        utils::parse_plugin_pin("git@github.com:RtopRS/Plugin").unwrap()
+    );
+    assert_eq!(
+        (
+            "https://github.com/RtopRS/Plugin".to_owned(),
+            Some(PluginPin::Tag("v1".to_owned()))
+        ),
+        utils::parse_plugin_pin("https://github.com/RtopRS/Plugin@v1").unwrap()
+    );
+    assert_eq!(
+        ("git@codeberg.org:plugin".to_owned(), None),
+        utils::parse_plugin_pin("git@codeberg.org:plugin").unwrap()
+    );
+    assert_eq!(
+        (
+            "git@codeberg.org:plugin".to_owned(),
+            Some(PluginPin::Tag("v1.2.0".to_owned()))
+        ),
+        utils::parse_plugin_pin("git@codeberg.org:plugin@v1.2.0").unwrap()
+    );
+    assert!(utils::parse_plugin_pin("--upload-pack=touch /tmp/pwned").is_err());
+    assert!(utils::parse_plugin_pin("foo@--upload-pack=touch").is_err());
+    assert!(utils::parse_plugin_pin("foo#-b").is_err());
+}
+
+#[test]
+fn test_compare_versions() {
+    assert_eq!(
+        Some(semver::Version::new(1, 2, 0)),
+        utils::parse_version("v1.2")
+    );
+    assert_eq!(None, utils::parse_version("nightly"));
+    assert_eq!(
+        VersionChange::Major,
+        utils::compare_versions("1.4.2", "2.0.0")
+    );
+    assert_eq!(
+        VersionChange::Minor,
+        utils::compare_versions("1.4.2", "1.5.0")
+    );
+    assert_eq!(
+        VersionChange::Patch,
+        utils::compare_versions("1.4.2", "1.4.3")
+    );
+    assert_eq!(
+        VersionChange::Patch,
+        utils::compare_versions("1.0.0-beta.1", "1.0.0")
+    );
+    assert_eq!(
+        VersionChange::Downgrade,
+        utils::compare_versions("1.10.0", "1.9.0")
+    );
+    assert_eq!(
+        VersionChange::UpToDate,
+        utils::compare_versions("v1.0", "1.0.0")
+    );
+    assert_eq!(
+        VersionChange::Unknown,
+        utils::compare_versions("nightly", "1.0.0")
+    );
+}
+
+#[test]
+fn test_verify_rtop_compatibility() {
+    let version: semver::Version = semver::Version::new(0, 2, 1);
+    assert!(utils::rtop_requirement_matches("^0.2", &version).unwrap());
+    assert!(!utils::rtop_requirement_matches(">=0.3", &version).unwrap());
+    assert!(utils::rtop_requirement_matches("not a range", &version).is_err());
+
+    assert!(utils::verify_rtop_compatibility("cpu", Some("^0.2"), Some(&version)).is_ok());
+    assert!(utils::verify_rtop_compatibility("cpu", None, Some(&version)).is_ok());
+    assert!(utils::verify_rtop_compatibility("cpu", Some("^0.3"), None).is_ok());
+    assert!(matches!(
+        utils::verify_rtop_compatibility("cpu", Some("^0.3"), Some(&version)),
+        Err(RtpmError::IncompatibleRtop { .. })
+    ));
+}
+
+#[test]
+fn test_repository_mirrors() {
+    let single: RepositoryManifest = serde_json::from_str(
+        r#"{"id": "rtop", "name": "Rtop", "description": "", "url": "https://github.com/RtopRS/PluginsRepository", "fallback_url": " "}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        single.mirrors(),
+        vec!["https://github.com/RtopRS/PluginsRepository"]
+    );
+
+    let list: RepositoryManifest = serde_json::from_str(
+        r#"{"id": "rtop", "name": "Rtop", "description": "", "url": "https://github.com/RtopRS/PluginsRepository", "fallback_url": ["https://gitlab.com/RtopRS/PluginsRepository", "https://github.com/RtopRS/PluginsRepository"]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        list.mirrors(),
+        vec![
+            "https://github.com/RtopRS/PluginsRepository",
+            "https://gitlab.com/RtopRS/PluginsRepository"
+        ]
+    );
+    assert!(serde_json::from_str::<RepositoryManifest>(
+        r#"{"id": "rtop", "name": "Rtop", "description": "", "url": "https://github.com/RtopRS/PluginsRepository", "fallback_url": ["--upload-pack=touch /tmp/pwned"]}"#,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_repository_entry_commit() {
+    assert!(serde_json::from_str::<RepositoryPlugin>(
+        r#"{"plugins": ["cpu", {"id": "clock", "commit": "3e1f0a9c3e1f0a9c3e1f0a9c3e1f0a9c3e1f0a9c"}]}"#,
+    )
+    .is_ok());
+    assert!(serde_json::from_str::<RepositoryPlugin>(
+        r#"{"plugins": [{"id": "clock", "commit": "3e1f0a9"}]}"#
+    )
+    .is_err());
+}
+
+#[test]
+fn test_plugin_ids() {
+    assert!(utils::is_plugin_id("cpu-usage"));
+    for id in ["", ".", "..", "../..", "a/b", "a\\b", "/etc"] {
+        assert!(!utils::is_plugin_id(id), "{}", id);
+    }
+    assert!(serde_json::from_str::<RepositoryPlugin>(r#"{"plugins": ["../.."]}"#).is_err());
+    assert!(serde_json::from_str::<PluginManifest>(
+        r#"{"id": "../..", "name": "CPU", "description": "", "version": "1.0.0", "url": "https://github.com/RtopRS/cpu"}"#,
+    )
+    .is_err());
+    assert!(serde_json::from_str::<PluginManifest>(
+        r#"{"id": "cpu", "name": "CPU", "description": "", "version": "1.0.0", "url": "https://github.com/RtopRS/cpu", "dependencies": {"..": "*"}}"#,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_set_once() {
+    let cell: OnceLock<u32> = OnceLock::new();
+    assert!(utils::set_once(&cell, 1, "test").is_ok());
+    assert!(utils::set_once(&cell, 1, "test").is_ok());
+    assert!(matches!(
+        utils::set_once(&cell, 2, "test"),
+        Err(RtpmError::InvalidInput(_))
+    ));
+    assert_eq!(Some(&1), cell.get());
+}
+
+#[test]
+fn test_directory_size() {
+    let temp: TempDir = temp_dir("directory_size");
+    let root: &Path = temp.path();
+    std::fs::create_dir_all(root.join("nested")).unwrap();
+    std::fs::write(root.join("a.txt"), [0_u8; 100]).unwrap();
+    std::fs::write(root.join("nested").join("b.txt"), [0_u8; 50]).unwrap();
+    assert_eq!(150, utils::directory_size(root).unwrap());
+    assert_eq!(0, utils::directory_size(&root.join("missing")).unwrap());
+}
+
+#[test]
+fn test_build_options_output_directory() {
+    let target_dir: PathBuf = PathBuf::from("/data/cache/target");
+    assert_eq!(
+        target_dir.join("release"),
+        BuildOptions::default().output_directory(&target_dir)
+    );
+    let build_options: BuildOptions = BuildOptions {
+        profile: Some("dev".to_owned()),
+        target: Some("aarch64-unknown-linux-gnu".to_owned()),
+        ..BuildOptions::default()
+    };
+    assert_eq!(
+        target_dir.join("aarch64-unknown-linux-gnu").join("debug"),
+        build_options.output_directory(&target_dir)
+    );
+}
+
+#[test]
+fn test_plugin_element_build_options() {
+    let temp: TempDir = temp_dir("plugin_element_build_options");
+    let path: PathBuf = temp.path().join("rtpm.json");
+    let element: RTPMConfigPluginElement = serde_json::from_str(
+        r#"{"id":"a","name":"A","version":"1.0.0","repo":"r","plugin_type":0,"build_options":{"features":["gpu"],"env":{"RUSTFLAGS":"-Copt-level=2"}}}"#,
+    )
+    .unwrap();
+    let config: RTPMConfig = RTPMConfig {
+        plugins: vec![element.clone()],
+        ..RTPMConfig::default()
+    };
+    utils::save_json_to_file(&config, path.clone()).unwrap();
+    let saved: RTPMConfig = utils::read_json_file(&path).unwrap();
+    assert_eq!(element.build_options, saved.plugins[0].build_options);
+    assert_eq!(
+        vec!["gpu".to_owned()],
+        saved.plugins[0].build_options.features
+    );
+    assert_eq!(
+        Some("-Copt-level=2"),
+        saved.plugins[0]
+            .build_options
+            .env
+            .get("RUSTFLAGS")
+            .map(String::as_str)
+    );
+    assert_eq!("release", saved.plugins[0].build_options.profile());
+}
+
+#[test]
+fn test_first_compiler_errors() {
+    let output: &str = "   Compiling demo v0.1.0 (/tmp/demo)
+warning: unused variable: `x`
+ --> src/lib.rs:1:5
+
+error[E0425]: cannot find value `y` in this scope
+ --> src/lib.rs:2:5
+  |
+2 |     y
+  |     ^ not found in this scope
+
+error: expected one of `!` or `::`, found `<eof>`
+ --> src/lib.rs:3:1
+
+error: could not compile `demo` due to 2 previous errors
+";
+    assert_eq!(
+        "error[E0425]: cannot find value `y` in this scope\n --> src/lib.rs:2:5\n  |\n2 |     y\n  |     ^ not found in this scope",
+        utils::first_compiler_errors(output, 1)
+    );
+    assert_eq!(
+        2,
+        utils::first_compiler_errors(output, 5)
+            .matches("error")
+            .count()
+    );
+    assert!(utils::first_compiler_errors("   Compiling demo\n", 3).is_empty());
+}